@@ -0,0 +1,232 @@
+//
+// Daniel Bauer (bauerda@pm.me)
+//
+
+use bevy::asset::{AssetLoader, LoadContext, LoadedAsset};
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use bevy::utils::{BoxedFuture, HashMap, HashSet};
+use serde::Deserialize;
+
+use super::atlas::*;
+use super::game::*;
+use super::packs::*;
+use super::states::*;
+
+const SPRITE_CLIPS: &str = "sprites.clips";
+
+// A clip missing in the clips file ends after this time, so nothing
+// waits forever for it to finish
+const MISSING_CLIP_DURATION: f32 = 1.;
+
+pub struct SpriteAnimationPlugin;
+
+impl Plugin for SpriteAnimationPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_asset::<SpriteClips>()
+            .init_asset_loader::<SpriteClipsLoader>()
+            .add_event::<AnimationFinished>()
+            .add_system(load_animation_clips.in_schedule(OnEnter(GameState::Start)))
+
+            // The animations are stepped after the gameplay systems picked
+            // the clips and the sequences for this frame
+            .add_system(
+                animate_sprites
+                    .in_base_set(CoreSet::PostUpdate)
+                    .before(update_atlas_sprites)
+                    .run_if(resource_exists::<SpriteClipsHandle>())
+            )
+        ;
+    }
+}
+
+#[derive(Clone, Copy, Default, Deserialize)]
+pub enum SpriteAnimationMode {
+    #[default]
+    Loop,
+    // Plays forward and backward again without repeating the first and
+    // the last frame
+    PingPong,
+    // Stops at the last frame and sends an animation finished event
+    Once,
+}
+
+// The frames of a clip, either a range of frame numbers including both
+// ends or a list of frame numbers
+#[derive(Deserialize)]
+pub enum SpriteFrames {
+    Range(usize, usize),
+    List(Vec<usize>),
+}
+
+impl SpriteFrames {
+    fn len(&self) -> usize {
+        match self {
+            SpriteFrames::Range(first, last) => last.saturating_sub(*first) + 1,
+            SpriteFrames::List(frames) => frames.len(),
+        }
+    }
+
+    fn get(&self, index: usize) -> usize {
+        match self {
+            SpriteFrames::Range(first, _) => first + index,
+            SpriteFrames::List(frames) => frames.get(index).copied().unwrap_or_default(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct SpriteClip {
+    pub frames: SpriteFrames,
+    pub fps: f32,
+    #[serde(default)]
+    pub mode: SpriteAnimationMode,
+}
+
+impl SpriteClip {
+    // The frame number shown after the given time and whether a one-shot
+    // clip has ended
+    pub fn sample(&self, elapsed: f32) -> (usize, bool) {
+        let count = self.frames.len().max(1);
+        let step = (elapsed * self.fps).max(0.) as usize;
+        let (index, is_finished) = match self.mode {
+            SpriteAnimationMode::Loop => (step % count, false),
+            SpriteAnimationMode::PingPong => {
+                let period = (2 * count).saturating_sub(2).max(1);
+                let index = step % period;
+                (if index < count { index } else { period - index }, false)
+            }
+            SpriteAnimationMode::Once => (step.min(count - 1), step >= count),
+        };
+        (self.frames.get(index), is_finished)
+    }
+}
+
+// The clips of the game by name, loaded from a RON map
+#[derive(TypeUuid)]
+#[uuid = "8f0b6c1e-2d4a-4b7f-a3e5-91c7d2f40b68"]
+pub struct SpriteClips {
+    clips: HashMap<String, SpriteClip>,
+}
+
+impl SpriteClips {
+    pub fn get(&self, name: &str) -> Option<&SpriteClip> {
+        self.clips.get(name)
+    }
+}
+
+#[derive(Default)]
+pub struct SpriteClipsLoader;
+
+impl AssetLoader for SpriteClipsLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let clips: HashMap<String, SpriteClip> = ron::de::from_bytes(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(SpriteClips { clips }));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["anim.ron"]
+    }
+}
+
+#[derive(Resource)]
+pub struct SpriteClipsHandle(pub Handle<SpriteClips>);
+
+// Plays a clip on the atlas sprite of the entity, the frame numbers of
+// the clip are appended to the sequence, e.g. "ghost.blinky" shows the
+// frames "ghost.blinky.0" and "ghost.blinky.1"
+#[derive(Component)]
+pub struct SpriteAnimation {
+    clip: String,
+    pub sequence: &'static str,
+    pub is_paused: bool,
+    elapsed: f32,
+    is_finished: bool,
+    // The sequence and frame number the atlas sprite shows, the frame name
+    // is only built again when one of them changes
+    shown: Option<(&'static str, usize)>,
+}
+
+impl SpriteAnimation {
+    pub fn new(clip: &str, sequence: &'static str) -> Self {
+        Self {
+            clip: clip.to_string(),
+            sequence,
+            is_paused: false,
+            elapsed: 0.,
+            is_finished: false,
+            shown: None,
+        }
+    }
+
+    pub fn paused(mut self) -> Self {
+        self.is_paused = true;
+        self
+    }
+
+    pub fn clip(&self) -> &str {
+        &self.clip
+    }
+}
+
+pub struct AnimationFinished {
+    pub entity: Entity,
+    pub clip: String,
+}
+
+fn load_animation_clips(mut commands: Commands, asset_server: Res<AssetServer>, pack_assets: Res<PackAssets>) {
+    commands.insert_resource(SpriteClipsHandle(pack_assets.load(&asset_server, SPRITE_CLIPS)));
+}
+
+// The animations run on the game clock, so they stand still while the
+// game is paused or frozen and follow the game speed. A missing clip is
+// warned about once and leaves the frame as it is.
+pub fn animate_sprites(
+    clips_handle: Res<SpriteClipsHandle>,
+    clips: Res<Assets<SpriteClips>>,
+    clock: Res<GameClock>,
+    mut finished_events: EventWriter<AnimationFinished>,
+    mut query: Query<(Entity, &mut SpriteAnimation, &mut AtlasSprite)>,
+    mut last_elapsed: Local<f32>,
+    mut missing_clips: Local<HashSet<String>>,
+) {
+    let delta = clock.elapsed_seconds() - *last_elapsed;
+    *last_elapsed = clock.elapsed_seconds();
+
+    let Some(clips) = clips.get(&clips_handle.0) else { return };
+    for (entity, mut animation, mut sprite) in query.iter_mut() {
+        if !animation.is_paused && !animation.is_finished {
+            animation.elapsed += delta;
+        }
+
+        let is_finished = match clips.get(&animation.clip) {
+            Some(clip) => {
+                let (frame, is_finished) = clip.sample(animation.elapsed);
+                let shown = Some((animation.sequence, frame));
+                if animation.shown != shown {
+                    animation.shown = shown;
+                    sprite.frame = format!("{}.{}", animation.sequence, frame);
+                }
+                is_finished
+            }
+            None => {
+                if missing_clips.insert(animation.clip.clone()) {
+                    warn!("Sprite clip {} is missing, it ends after {}s", animation.clip, MISSING_CLIP_DURATION);
+                }
+                animation.elapsed >= MISSING_CLIP_DURATION
+            }
+        };
+        if is_finished && !animation.is_finished {
+            animation.is_finished = true;
+            finished_events.send(AnimationFinished { entity, clip: animation.clip.clone() });
+        }
+    }
+}
@@ -0,0 +1,211 @@
+//
+// Daniel Bauer (bauerda@pm.me)
+//
+
+use bevy::asset::{AssetLoader, LoadContext, LoadedAsset};
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use bevy::utils::{BoxedFuture, HashMap};
+use serde::Deserialize;
+
+use super::packs::*;
+use super::states::*;
+
+const SPRITE_ATLASES: &str = "sprites.atlases";
+
+pub struct SpriteAtlasPlugin;
+
+impl Plugin for SpriteAtlasPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_asset::<SpriteAtlases>()
+            .init_asset_loader::<SpriteAtlasesLoader>()
+            .add_system(
+                preload_atlas_images
+                    .in_set(OnUpdate(GameState::Loading))
+                    .before(finish_loading)
+            )
+            .add_system(
+                update_atlas_sprites
+                    .in_base_set(CoreSet::PostUpdate)
+                    .run_if(resource_exists::<SpriteAtlasesHandle>())
+            )
+        ;
+    }
+}
+
+// A cell of an atlas, the sprite can be rotated counterclockwise by the
+// given degrees and flipped, so one cell can serve several directions
+#[derive(Clone, Copy, Deserialize)]
+pub struct AtlasFrame {
+    pub cell: usize,
+    #[serde(default)]
+    pub rotation: f32,
+    #[serde(default)]
+    pub flip_x: bool,
+}
+
+// An image made of a grid of cells of the same size, variants are other
+// images with the same layout, e.g. with other colors
+#[derive(Deserialize)]
+pub struct AtlasDescription {
+    pub image: String,
+    #[serde(default)]
+    pub variants: HashMap<String, String>,
+    pub cell_size: (f32, f32),
+    pub columns: usize,
+    pub rows: usize,
+    pub frames: HashMap<String, AtlasFrame>,
+}
+
+impl AtlasDescription {
+    fn image(&self, variant: Option<&str>) -> &str {
+        variant
+            .and_then(|variant| self.variants.get(variant))
+            .unwrap_or(&self.image)
+    }
+}
+
+// The atlases of the game by name, the frame names are unique across all
+// atlases, e.g. "ghost.blinky.0" or "pacman.death.5"
+#[derive(TypeUuid)]
+#[uuid = "3e9a7d52-1c6b-4f08-b2d4-6a5f0e8c7913"]
+pub struct SpriteAtlases {
+    atlases: HashMap<String, AtlasDescription>,
+    frames: HashMap<String, String>,
+}
+
+impl SpriteAtlases {
+    fn new(atlases: HashMap<String, AtlasDescription>) -> Self {
+        let mut frames = HashMap::default();
+        for (atlas_name, atlas) in atlases.iter() {
+            for frame_name in atlas.frames.keys() {
+                if frames.insert(frame_name.clone(), atlas_name.clone()).is_some() {
+                    warn!("Sprite frame {} is defined in more than one atlas", frame_name);
+                }
+            }
+        }
+        Self { atlases, frames }
+    }
+
+    pub fn find(&self, frame_name: &str) -> Option<(&AtlasDescription, AtlasFrame)> {
+        let atlas = self.atlases.get(self.frames.get(frame_name)?)?;
+        Some((atlas, *atlas.frames.get(frame_name)?))
+    }
+}
+
+#[derive(Default)]
+pub struct SpriteAtlasesLoader;
+
+impl AssetLoader for SpriteAtlasesLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let atlases: HashMap<String, AtlasDescription> = ron::de::from_bytes(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(SpriteAtlases::new(atlases)));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["atlas.ron"]
+    }
+}
+
+#[derive(Resource)]
+pub struct SpriteAtlasesHandle(pub Handle<SpriteAtlases>);
+
+// Shows the named frame on the texture atlas sprite of the entity, the
+// texture atlas and the index are looked up in the atlas descriptions
+#[derive(Component)]
+pub struct AtlasSprite {
+    pub frame: String,
+    pub variant: Option<String>,
+}
+
+impl AtlasSprite {
+    pub fn new(frame: &str) -> Self {
+        Self {
+            frame: frame.to_string(),
+            variant: None,
+        }
+    }
+
+    pub fn with_variant(mut self, variant: Option<&str>) -> Self {
+        self.variant = variant.map(str::to_string);
+        self
+    }
+}
+
+// The images of the atlases and their variants are loaded with the other
+// assets of the pack once the atlas descriptions are loaded
+fn preload_atlas_images(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    atlases: Res<Assets<SpriteAtlases>>,
+    mut pack_assets: ResMut<PackAssets>,
+    mut is_preloaded: Local<bool>,
+) {
+    if *is_preloaded || !pack_assets.is_resolved() { return; }
+
+    let handle = pack_assets.load(&asset_server, SPRITE_ATLASES);
+    let Some(sprite_atlases) = atlases.get(&handle) else { return };
+    for atlas in sprite_atlases.atlases.values() {
+        for image in std::iter::once(&atlas.image).chain(atlas.variants.values()) {
+            pack_assets.preload(asset_server.load_untyped(image.as_str()));
+        }
+    }
+    commands.insert_resource(SpriteAtlasesHandle(handle));
+    *is_preloaded = true;
+}
+
+// Resolve the frames of the changed sprites, all sprites are resolved
+// again when the atlas descriptions are loaded or reloaded
+#[allow(clippy::type_complexity)]
+pub fn update_atlas_sprites(
+    atlases_handle: Res<SpriteAtlasesHandle>,
+    atlases: Res<Assets<SpriteAtlases>>,
+    asset_server: Res<AssetServer>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    mut atlas_events: EventReader<AssetEvent<SpriteAtlases>>,
+    mut query: Query<(Ref<AtlasSprite>, &mut Handle<TextureAtlas>, &mut TextureAtlasSprite, &mut Transform)>,
+    mut texture_atlas_handles: Local<HashMap<String, Handle<TextureAtlas>>>,
+) {
+    let is_reloaded = atlas_events.iter().count() > 0;
+    if is_reloaded {
+        texture_atlas_handles.clear();
+    }
+    let Some(atlases) = atlases.get(&atlases_handle.0) else { return };
+
+    for (atlas_sprite, mut texture_atlas, mut sprite, mut transform) in query.iter_mut() {
+        if !is_reloaded && !atlas_sprite.is_changed() { continue; }
+
+        let Some((atlas, frame)) = atlases.find(&atlas_sprite.frame) else {
+            warn!("Sprite frame {} is not defined", atlas_sprite.frame);
+            continue;
+        };
+
+        let image = atlas.image(atlas_sprite.variant.as_deref());
+        let handle = texture_atlas_handles
+            .entry(image.to_string())
+            .or_insert_with(|| texture_atlases.add(TextureAtlas::from_grid(
+                asset_server.load(image),
+                Vec2::new(atlas.cell_size.0, atlas.cell_size.1),
+                atlas.columns,
+                atlas.rows,
+                None,
+                None
+            )));
+        if *texture_atlas != *handle {
+            *texture_atlas = handle.clone();
+        }
+        if sprite.index != frame.cell || sprite.flip_x != frame.flip_x {
+            sprite.index = frame.cell;
+            sprite.flip_x = frame.flip_x;
+        }
+        transform.rotation = Quat::from_rotation_z(frame.rotation.to_radians());
+    }
+}
@@ -60,7 +60,7 @@ const OBSTACLES: [Rect; 28] = [
 ];
 
 #[derive(Component, Clone, Copy, PartialEq, Eq)]
-struct Rect {
+pub struct Rect {
     pub x: i32,
     pub y: i32,
     pub w: i32,
@@ -75,6 +75,28 @@ impl Rect {
     }
 }
 
+pub fn obstacles() -> &'static [Rect] {
+    &OBSTACLES
+}
+
+// The area units are allowed to move in outside of the tunnel
+pub fn map_bounds() -> Rect {
+    Rect {
+        x: MAZE_START_X as i32,
+        y: MAZE_START_Y as i32,
+        w: (MAZE_END_X - MAZE_START_X) as i32,
+        h: (MAZE_END_Y - MAZE_START_Y) as i32,
+    }
+}
+
+// The line units move along through the tunnel from the left to the right
+pub fn tunnel_line() -> (UnitPosition, UnitPosition) {
+    (
+        UnitPosition { x: TUNNEL_X_LEFT, y: TUNNEL_Y },
+        UnitPosition { x: TUNNEL_X_RIGHT, y: TUNNEL_Y },
+    )
+}
+
 pub fn check_in_map(x: i32, y: i32, size: u32) -> bool {
     let offset = (size / 2) as i32;
     x - offset >= MAZE_START_X as i32 && x + offset <= MAZE_END_X as i32
@@ -103,20 +125,54 @@ pub fn unit_can_move_in_direction(
     current_pos: &UnitPosition,
     direction: UnitDirection,
 ) -> bool {
-    let mut new_pos = current_pos.clone();
+    let mut new_pos = *current_pos;
     new_pos.move_in_direction(direction);
     unit_can_move(&new_pos)
 }
 
 pub fn units_collide(a_pos: &UnitPosition, a_size: u32, b_pos: &UnitPosition, b_size: u32) -> bool {
-    if let Some(_) = collide(
+    collide(
         a_pos.to_vec3(),
         Vec2 { x: a_size as f32, y: a_size as f32 },
         b_pos.to_vec3(),
-        Vec2 { x: b_size as f32, y: b_size as f32}) {
-        true
-    } else {
-        false
+        Vec2 { x: b_size as f32, y: b_size as f32}).is_some()
+}
+
+// Earliest time in the range [0, 1] of the tick at which two units collide
+// while they walk their paths of pixel steps. The steps of a unit are
+// spread evenly over the tick, so the units are compared after every step
+// of either unit and no contact is missed, even if a unit turned or was
+// teleported through the tunnel.
+pub fn units_collide_along_paths(
+    a_path: &[UnitPosition],
+    a_size: u32,
+    b_path: &[UnitPosition],
+    b_size: u32,
+) -> Option<f32> {
+    let a_steps = a_path.len().checked_sub(1)?;
+    let b_steps = b_path.len().checked_sub(1)?;
+    let time = |step: usize, steps: usize| if steps == 0 { 0. } else { step as f32 / steps as f32 };
+
+    let (mut a, mut b) = (0, 0);
+    loop {
+        if units_collide(&a_path[a], a_size, &b_path[b], b_size) {
+            return Some(time(a, a_steps).max(time(b, b_steps)));
+        }
+
+        // Take the step which comes next, steps at the same time together
+        let a_next = (a < a_steps).then(|| time(a + 1, a_steps));
+        let b_next = (b < b_steps).then(|| time(b + 1, b_steps));
+        match (a_next, b_next) {
+            (None, None) => return None,
+            (Some(a_time), Some(b_time)) if a_time < b_time => a += 1,
+            (Some(a_time), Some(b_time)) if b_time < a_time => b += 1,
+            (Some(_), Some(_)) => {
+                a += 1;
+                b += 1;
+            }
+            (Some(_), None) => a += 1,
+            (None, Some(_)) => b += 1,
+        }
     }
 }
 
@@ -129,3 +185,71 @@ pub fn teleport_tunnel(pos: &mut UnitPosition) {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The positions of a unit which takes the given runs of pixel steps
+    fn walk(start: UnitPosition, runs: &[(UnitDirection, u32)]) -> Vec<UnitPosition> {
+        let mut pos = start;
+        let mut path = vec![pos];
+        for (direction, steps) in runs {
+            for _ in 0..*steps {
+                pos.move_in_direction(*direction);
+                path.push(pos);
+            }
+        }
+        path
+    }
+
+    #[test]
+    fn units_passing_head_on_in_one_tick_collide() {
+        // Both units move 100 pixels, they swapped places at the end
+        let a = walk(UnitPosition { x: 1000, y: 1083 }, &[(UnitDirection::Right, 100)]);
+        let b = walk(UnitPosition { x: 1100, y: 1083 }, &[(UnitDirection::Left, 100)]);
+        let time = units_collide_along_paths(&a, UNIT_HITBOX_SIZE, &b, UNIT_HITBOX_SIZE);
+        assert_eq!(time, Some(0.41));
+    }
+
+    #[test]
+    fn units_on_parallel_lines_do_not_collide() {
+        let a = walk(UnitPosition { x: 1000, y: 1083 }, &[(UnitDirection::Right, 100)]);
+        let b = walk(UnitPosition { x: 1100, y: 1183 }, &[(UnitDirection::Left, 100)]);
+        assert_eq!(units_collide_along_paths(&a, UNIT_HITBOX_SIZE, &b, UNIT_HITBOX_SIZE), None);
+    }
+
+    #[test]
+    fn units_collide_at_the_end_of_a_shorter_path() {
+        // The unit standing still is met when the other one arrives
+        let a = walk(UnitPosition { x: 1000, y: 1083 }, &[(UnitDirection::Right, 50)]);
+        let b = [UnitPosition { x: 1069, y: 1083 }];
+        assert_eq!(units_collide_along_paths(&a, UNIT_HITBOX_SIZE, &b, UNIT_HITBOX_SIZE), Some(1.));
+    }
+
+    #[test]
+    fn units_turning_a_corner_are_checked_along_their_turn() {
+        let corner = walk(
+            UnitPosition { x: 1000, y: 1000 },
+            &[(UnitDirection::Up, 50), (UnitDirection::Right, 50)],
+        );
+
+        // Inside of the corner, a straight line from the start to the end
+        // of the path would cross this unit
+        let inside = [UnitPosition { x: 1030, y: 1020 }];
+        assert_eq!(units_collide_along_paths(&corner, UNIT_HITBOX_SIZE, &inside, UNIT_HITBOX_SIZE), None);
+
+        // Next to the first leg, a straight line would pass it
+        let outside = [UnitPosition { x: 1010, y: 1045 }];
+        let time = units_collide_along_paths(&corner, UNIT_HITBOX_SIZE, &outside, UNIT_HITBOX_SIZE);
+        assert_eq!(time, Some(0.26));
+    }
+
+    #[test]
+    fn units_teleported_through_the_tunnel_do_not_sweep_the_maze() {
+        let a = walk(UnitPosition { x: TUNNEL_X_LEFT + 2, y: TUNNEL_Y }, &[(UnitDirection::Left, 4)]);
+        let b = [UnitPosition { x: MAZE_WIDTH as i32 / 2, y: TUNNEL_Y }];
+        assert_eq!(a.last(), Some(&UnitPosition { x: TUNNEL_X_RIGHT - 2, y: TUNNEL_Y }));
+        assert_eq!(units_collide_along_paths(&a, UNIT_HITBOX_SIZE, &b, UNIT_HITBOX_SIZE), None);
+    }
+}
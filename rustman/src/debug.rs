@@ -0,0 +1,285 @@
+//
+// Daniel Bauer (bauerda@pm.me)
+//
+
+use bevy::prelude::*;
+
+use super::collision::*;
+use super::game::*;
+use super::ghosts::*;
+use super::maze::*;
+use super::packs::*;
+use super::pacman::*;
+use super::scaling::*;
+use super::states::*;
+use super::unit::*;
+
+const DEBUG_Z: f32 = 20.;
+const DEBUG_LINE_WIDTH: f32 = 6.;
+const DEBUG_DIRECTION_LENGTH: f32 = 90.;
+const DEBUG_MARKER_SIZE: f32 = 40.;
+const DEBUG_PATH_DOT_SIZE: f32 = 14.;
+const DEBUG_PATH_TILES: usize = 12;
+const DEBUG_FONT: &str = "font.debug";
+const DEBUG_FONT_SIZE: f32 = 14.;
+
+const DEBUG_COLOR_OBSTACLE: Color = Color::rgba(0.2, 0.4, 1., 0.35);
+const DEBUG_COLOR_BOUNDS: Color = Color::rgba(0.2, 1., 0.2, 0.8);
+const DEBUG_COLOR_TUNNEL: Color = Color::rgba(1., 1., 0.2, 0.8);
+const DEBUG_COLOR_UNIT: Color = Color::rgba(1., 1., 1., 0.6);
+const DEBUG_COLOR_HITBOX: Color = Color::rgba(1., 0.1, 0.1, 0.9);
+
+pub struct DebugPlugin;
+
+impl Plugin for DebugPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<DebugOverlay>()
+            .add_systems((
+                toggle_debug_overlay,
+                update_debug_panel
+                    .after(toggle_debug_overlay)
+                    .run_if(is_debug_overlay_enabled),
+            ))
+
+            // The shapes are drawn after the units moved, so they are not
+            // a frame behind
+            .add_system(
+                draw_debug_shapes
+                    .in_base_set(CoreSet::PostUpdate)
+                    .run_if(is_debug_overlay_enabled)
+            )
+        ;
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct DebugOverlay {
+    pub is_enabled: bool,
+}
+
+#[derive(Component)]
+struct DebugShape;
+
+#[derive(Component)]
+struct DebugPanel;
+
+fn is_debug_overlay_enabled(overlay: Res<DebugOverlay>) -> bool {
+    overlay.is_enabled
+}
+
+fn ghost_color(ghost_id: GhostId) -> Color {
+    match ghost_id {
+        GhostId::Blinky => Color::RED,
+        GhostId::Pinky => Color::PINK,
+        GhostId::Inky => Color::CYAN,
+        GhostId::Clyde => Color::ORANGE,
+        GhostId::Frightened | GhostId::FrightenedBlink => Color::BLUE,
+    }
+}
+
+fn toggle_debug_overlay(
+    mut commands: Commands,
+    keys: Res<Input<KeyCode>>,
+    mut overlay: ResMut<DebugOverlay>,
+    query_shapes: Query<Entity, With<DebugShape>>,
+    query_panel: Query<Entity, With<DebugPanel>>,
+) {
+    if !keys.just_pressed(KeyCode::F3) { return; }
+
+    overlay.is_enabled = !overlay.is_enabled;
+    if !overlay.is_enabled {
+        for entity in query_shapes.iter().chain(query_panel.iter()) {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+// A rectangle of the overlay in world coordinates
+struct DebugRect {
+    translation: Vec3,
+    size: Vec2,
+    color: Color,
+}
+
+// Collects rectangles given in maze units, they are drawn with the sprites
+// of the shape pool
+#[derive(Default)]
+struct DebugPainter {
+    rects: Vec<DebugRect>,
+}
+
+impl DebugPainter {
+    fn rect(&mut self, center_x: f32, center_y: f32, width: f32, height: f32, color: Color) {
+        self.rects.push(DebugRect {
+            translation: unit_translation(center_x, center_y).extend(DEBUG_Z),
+            size: Vec2::new(width, height),
+            color,
+        });
+    }
+
+    fn outline(&mut self, center_x: f32, center_y: f32, width: f32, height: f32, color: Color) {
+        let (half_width, half_height) = (width / 2., height / 2.);
+        self.rect(center_x, center_y - half_height, width, DEBUG_LINE_WIDTH, color);
+        self.rect(center_x, center_y + half_height, width, DEBUG_LINE_WIDTH, color);
+        self.rect(center_x - half_width, center_y, DEBUG_LINE_WIDTH, height, color);
+        self.rect(center_x + half_width, center_y, DEBUG_LINE_WIDTH, height, color);
+    }
+
+    fn unit_box(&mut self, pos: &UnitPosition, size: u32, color: Color) {
+        self.outline(pos.x as f32, pos.y as f32, size as f32, size as f32, color);
+    }
+
+    fn direction(&mut self, pos: &UnitPosition, direction: UnitDirection, color: Color) {
+        let (x, y) = (pos.x as f32, pos.y as f32);
+        let half = DEBUG_DIRECTION_LENGTH / 2.;
+        match direction {
+            UnitDirection::Left => self.rect(x - half, y, DEBUG_DIRECTION_LENGTH, DEBUG_LINE_WIDTH, color),
+            UnitDirection::Right => self.rect(x + half, y, DEBUG_DIRECTION_LENGTH, DEBUG_LINE_WIDTH, color),
+            UnitDirection::Up => self.rect(x, y + half, DEBUG_LINE_WIDTH, DEBUG_DIRECTION_LENGTH, color),
+            UnitDirection::Down => self.rect(x, y - half, DEBUG_LINE_WIDTH, DEBUG_DIRECTION_LENGTH, color),
+            UnitDirection::None => {}
+        }
+    }
+
+    fn marker(&mut self, pos: &UnitPosition, color: Color) {
+        let (x, y) = (pos.x as f32, pos.y as f32);
+        self.rect(x, y, DEBUG_MARKER_SIZE, DEBUG_LINE_WIDTH, color);
+        self.rect(x, y, DEBUG_LINE_WIDTH, DEBUG_MARKER_SIZE, color);
+    }
+}
+
+// The sprites of the shapes are kept as a pool, each frame they are moved
+// onto the rectangles to draw, missing sprites are spawned and the unused
+// ones are hidden
+#[allow(clippy::type_complexity)]
+fn draw_debug_shapes(
+    mut commands: Commands,
+    elroy: Res<Elroy>,
+    mut query_shapes: Query<(&mut Sprite, &mut Transform, &mut Visibility), With<DebugShape>>,
+    query_units: Query<&UnitPosition, Or<(With<Pacman>, With<Ghost>)>>,
+    query_ghosts: Query<(&Ghost, &UnitPosition)>,
+    query_pacman: Query<&UnitPosition, With<Pacman>>,
+) {
+    let mut painter = DebugPainter::default();
+
+    // Maze
+    for obstacle in obstacles() {
+        let (width, height) = (obstacle.w as f32, obstacle.h as f32);
+        painter.rect(obstacle.x as f32 + width / 2., obstacle.y as f32 + height / 2., width, height, DEBUG_COLOR_OBSTACLE);
+    }
+    let bounds = map_bounds();
+    let (width, height) = (bounds.w as f32, bounds.h as f32);
+    painter.outline(bounds.x as f32 + width / 2., bounds.y as f32 + height / 2., width, height, DEBUG_COLOR_BOUNDS);
+    let (tunnel_left, tunnel_right) = tunnel_line();
+    let tunnel_width = (tunnel_right.x - tunnel_left.x) as f32;
+    painter.rect(tunnel_left.x as f32 + tunnel_width / 2., tunnel_left.y as f32, tunnel_width, DEBUG_LINE_WIDTH, DEBUG_COLOR_TUNNEL);
+
+    // Units
+    for pos in query_units.iter() {
+        painter.unit_box(pos, UNIT_SIZE, DEBUG_COLOR_UNIT);
+        painter.unit_box(pos, UNIT_HITBOX_SIZE, DEBUG_COLOR_HITBOX);
+    }
+
+    // Ghosts
+    let pac_pos = query_pacman.iter().next();
+    for (ghost, ghost_pos) in query_ghosts.iter() {
+        let color = ghost_color(ghost.ghost_id);
+        painter.direction(ghost_pos, ghost.current_direction, color);
+        if !ghost.is_moved_out || ghost.is_eaten { continue; }
+
+        let target = pac_pos.filter(|_| ghost.is_elroy(&elroy));
+        if let Some(target) = target {
+            painter.marker(target, color);
+        }
+        for pos in planned_path(ghost_pos, ghost.current_direction, target, DEBUG_PATH_TILES) {
+            painter.rect(pos.x as f32, pos.y as f32, DEBUG_PATH_DOT_SIZE, DEBUG_PATH_DOT_SIZE, color);
+        }
+    }
+
+    let mut rects = painter.rects.into_iter();
+    for (mut sprite, mut transform, mut visibility) in query_shapes.iter_mut() {
+        let Some(rect) = rects.next() else {
+            if *visibility != Visibility::Hidden {
+                *visibility = Visibility::Hidden;
+            }
+            continue;
+        };
+        if sprite.color != rect.color {
+            sprite.color = rect.color;
+        }
+        if sprite.custom_size != Some(rect.size) {
+            sprite.custom_size = Some(rect.size);
+        }
+        if transform.translation != rect.translation {
+            transform.translation = rect.translation;
+        }
+        if *visibility != Visibility::Inherited {
+            *visibility = Visibility::Inherited;
+        }
+    }
+    for rect in rects {
+        commands.spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color: rect.color,
+                    custom_size: Some(rect.size),
+                    ..default()
+                },
+                transform: Transform::from_translation(rect.translation),
+                ..default()
+            },
+            DebugShape,
+        ));
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn update_debug_panel(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    pack_assets: Res<PackAssets>,
+    game_state: Res<State<GameState>>,
+    pacman_state: Res<State<PacmanState>>,
+    clock: Res<GameClock>,
+    rng: Res<GameRng>,
+    pellets: Res<Pellets>,
+    mut query_panel: Query<&mut Text, With<DebugPanel>>,
+    mut fps: Local<f32>,
+    time: Res<Time>,
+) {
+    // Smooth the frames per second over the last frames
+    if time.delta_seconds() > 0. {
+        *fps = *fps * 0.9 + 0.1 / time.delta_seconds();
+    }
+
+    let value = format!(
+        "FPS     {:.0}\nGAME    {:?}\nPACMAN  {:?}\nSEED    {}\nTICK    {}\nPELLETS {}/{}",
+        *fps, game_state.0, pacman_state.0, rng.seed(), clock.tick(), pellets.remaining(), pellets.total(),
+    );
+
+    if let Some(mut text) = query_panel.iter_mut().next() {
+        text.sections[0].value = value;
+        return;
+    }
+
+    let mut panel = TextBundle::from_section(
+        value,
+        TextStyle {
+            font: pack_assets.load(&asset_server, DEBUG_FONT),
+            font_size: DEBUG_FONT_SIZE,
+            color: Color::WHITE,
+        },
+    ).with_style(Style {
+        position_type: PositionType::Absolute,
+        position: UiRect {
+            left: Val::Px(10.),
+            top: Val::Px(60.),
+            ..default()
+        },
+        ..default()
+    });
+    panel.background_color = Color::rgba(0., 0., 0., 0.6).into();
+    panel.z_index = ZIndex::Global(5);
+    commands.spawn((panel, DebugPanel));
+}
@@ -0,0 +1,84 @@
+//
+// Daniel Bauer (bauerda@pm.me)
+//
+
+use bevy::prelude::*;
+
+use super::ghosts::*;
+use super::unit::*;
+
+// The gameplay events are sent by the simulation, scoring, sound and the
+// user interface react to them in their own systems
+
+pub struct EventsPlugin;
+
+impl Plugin for EventsPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_event::<DotEaten>()
+            .add_event::<EnergizerEaten>()
+            .add_event::<GhostEaten>()
+            .add_event::<PacmanDied>()
+            .add_event::<RoundWon>()
+            .add_event::<ExtraLife>()
+            .add_system(log_events.in_base_set(CoreSet::PostUpdate))
+        ;
+    }
+}
+
+pub struct DotEaten {
+    pub position: UnitPosition,
+}
+
+pub struct EnergizerEaten {
+    pub position: UnitPosition,
+}
+
+pub struct GhostEaten {
+    pub ghost: GhostId,
+    pub points: u32,
+    pub position: UnitPosition,
+}
+
+pub struct PacmanDied {
+    pub position: UnitPosition,
+}
+
+pub struct RoundWon {
+    pub round: u32,
+}
+
+// Sent when pacman gets another life, the game does not award any extra
+// lives yet
+#[allow(dead_code)]
+pub struct ExtraLife {
+    pub lifes: u32,
+}
+
+fn log_events(
+    mut dot_events: EventReader<DotEaten>,
+    mut energizer_events: EventReader<EnergizerEaten>,
+    mut ghost_events: EventReader<GhostEaten>,
+    mut died_events: EventReader<PacmanDied>,
+    mut round_won_events: EventReader<RoundWon>,
+    mut extra_life_events: EventReader<ExtraLife>,
+) {
+    for event in dot_events.iter() {
+        debug!("Dot eaten at {}, {}", event.position.x, event.position.y);
+    }
+    for event in energizer_events.iter() {
+        debug!("Energizer eaten at {}, {}", event.position.x, event.position.y);
+    }
+    for event in ghost_events.iter() {
+        debug!("{:?} eaten at {}, {} for {} points", event.ghost, event.position.x, event.position.y, event.points);
+    }
+    for event in died_events.iter() {
+        debug!("Pacman died at {}, {}", event.position.x, event.position.y);
+    }
+    for event in round_won_events.iter() {
+        debug!("Round {} won", event.round);
+    }
+    for event in extra_life_events.iter() {
+        debug!("Extra life, {} lifes left", event.lifes);
+    }
+}
@@ -2,30 +2,112 @@
 // Daniel Bauer (bauerda@pm.me)
 //
 
+use std::time::Duration;
+
 use bevy::prelude::*;
+use rand::{rngs::StdRng, RngCore, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use super::events::*;
+use super::settings::*;
+use super::states::*;
+use super::storage;
 
 pub const POINTS_DOT: u32 = 10;
 pub const POINTS_ENERGIZER: u32 = 50;
 pub const POINTS_GHOST: u32 = 200;
 
+pub const FRIGHTENED_BLINK_DURATION: f32 = 0.4;
+
+// Gameplay freezes for this time after a ghost is eaten
+pub const FREEZE_DURATION_GHOST_EATEN: f32 = 1.;
+
+// The gameplay is simulated in ticks of this duration, independent of
+// the frame rate
+pub const SIMULATION_TICK: f32 = 1. / 120.;
+
+const HIGH_SCORE_KEY: &str = "highscore";
+
+// Level fruit, frightened time, number of blinks and remaining dots for
+// the Cruise Elroy stages per round as in the arcade, rounds after the
+// last entry use the last entry
+const ROUND_SPECS: [RoundSpec; 19] = [
+    RoundSpec { fruit: Fruit::Cherry, frightened_duration: 6., frightened_blinks: 5, elroy1_dots: 20, elroy2_dots: 10 },
+    RoundSpec { fruit: Fruit::Strawberry, frightened_duration: 5., frightened_blinks: 5, elroy1_dots: 30, elroy2_dots: 15 },
+    RoundSpec { fruit: Fruit::Orange, frightened_duration: 4., frightened_blinks: 5, elroy1_dots: 40, elroy2_dots: 20 },
+    RoundSpec { fruit: Fruit::Orange, frightened_duration: 3., frightened_blinks: 5, elroy1_dots: 40, elroy2_dots: 20 },
+    RoundSpec { fruit: Fruit::Apple, frightened_duration: 2., frightened_blinks: 5, elroy1_dots: 40, elroy2_dots: 20 },
+    RoundSpec { fruit: Fruit::Apple, frightened_duration: 5., frightened_blinks: 5, elroy1_dots: 50, elroy2_dots: 25 },
+    RoundSpec { fruit: Fruit::Melon, frightened_duration: 2., frightened_blinks: 5, elroy1_dots: 50, elroy2_dots: 25 },
+    RoundSpec { fruit: Fruit::Melon, frightened_duration: 2., frightened_blinks: 5, elroy1_dots: 50, elroy2_dots: 25 },
+    RoundSpec { fruit: Fruit::Galaxian, frightened_duration: 1., frightened_blinks: 3, elroy1_dots: 60, elroy2_dots: 30 },
+    RoundSpec { fruit: Fruit::Galaxian, frightened_duration: 5., frightened_blinks: 5, elroy1_dots: 60, elroy2_dots: 30 },
+    RoundSpec { fruit: Fruit::Bell, frightened_duration: 2., frightened_blinks: 5, elroy1_dots: 60, elroy2_dots: 30 },
+    RoundSpec { fruit: Fruit::Bell, frightened_duration: 1., frightened_blinks: 3, elroy1_dots: 80, elroy2_dots: 40 },
+    RoundSpec { fruit: Fruit::Key, frightened_duration: 1., frightened_blinks: 3, elroy1_dots: 80, elroy2_dots: 40 },
+    RoundSpec { fruit: Fruit::Key, frightened_duration: 3., frightened_blinks: 5, elroy1_dots: 80, elroy2_dots: 40 },
+    RoundSpec { fruit: Fruit::Key, frightened_duration: 1., frightened_blinks: 3, elroy1_dots: 100, elroy2_dots: 50 },
+    RoundSpec { fruit: Fruit::Key, frightened_duration: 1., frightened_blinks: 3, elroy1_dots: 100, elroy2_dots: 50 },
+    RoundSpec { fruit: Fruit::Key, frightened_duration: 0., frightened_blinks: 0, elroy1_dots: 100, elroy2_dots: 50 },
+    RoundSpec { fruit: Fruit::Key, frightened_duration: 1., frightened_blinks: 3, elroy1_dots: 100, elroy2_dots: 50 },
+    RoundSpec { fruit: Fruit::Key, frightened_duration: 0., frightened_blinks: 0, elroy1_dots: 120, elroy2_dots: 60 },
+];
+
 pub struct GamePlugin;
 
 impl Plugin for GamePlugin {
     fn build(&self, app: &mut App) {
         app
             .insert_resource(Game::new())
+            .init_resource::<GameClock>()
+            .init_resource::<GameRng>()
+            .insert_resource(storage::load::<HighScore>(HIGH_SCORE_KEY).unwrap_or_default())
+            .insert_resource(FixedTime::new_from_secs(SIMULATION_TICK))
+            .edit_schedule(CoreSchedule::FixedUpdate, |schedule| {
+                schedule.configure_sets((
+                    SimulationSet::Clock,
+                    SimulationSet::Movement,
+                    SimulationSet::Contacts,
+                ).chain());
+            })
+
+            // Simulation Ticks
+            .add_systems((
+                update_game_clock
+                    .in_schedule(CoreSchedule::FixedUpdate)
+                    .in_set(SimulationSet::Clock),
+                freeze_on_ghost_eaten
+                    .in_schedule(CoreSchedule::FixedUpdate)
+                    .after(SimulationSet::Contacts),
+            ))
+
+            .add_systems((
+                update_game_speed,
+                apply_game_speed_to_simulation,
+                update_points,
+                save_high_score
+                    .in_schedule(OnEnter(GameState::GameOver)),
+            ))
         ;
     }
 }
 
+// The steps of a simulation tick in the fixed update schedule, the units
+// move after the clock advanced and touch each other after they moved
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum SimulationSet {
+    Clock,
+    Movement,
+    Contacts,
+}
+
 #[derive(Resource)]
 pub struct Game {
     pub round: u32,
     pub points: u32,
     pub lifes: u32,
+    pub speed_percent: u32,
     pub elapsed_time_state: f32,
-    pub elapsed_time_sound: f32,
-    pub elapsed_time_blink: f32,
 }
 
 impl Game {
@@ -34,9 +116,265 @@ impl Game {
             round: 1,
             points: 0,
             lifes: 3,
+            speed_percent: 100,
             elapsed_time_state: 0.,
-            elapsed_time_sound: 0.,
-            elapsed_time_blink: 0.,
         }
     }
+
+    pub fn round_spec(&self) -> &'static RoundSpec {
+        RoundSpec::get(self.round)
+    }
+
+    pub fn speed(&self) -> f32 {
+        self.speed_percent as f32 / 100.
+    }
+}
+
+// The clock of the gameplay, it advances by one simulation tick at a time
+// and stands still while the game is paused or frozen, so all gameplay
+// timers are based on it instead of the real time
+#[derive(Resource, Default)]
+pub struct GameClock {
+    elapsed: f32,
+    delta: f32,
+    tick: u64,
+    freeze_remaining: f32,
+}
+
+impl GameClock {
+    pub fn elapsed_seconds(&self) -> f32 {
+        self.elapsed
+    }
+
+    pub fn delta_seconds(&self) -> f32 {
+        self.delta
+    }
+
+    // Number of simulation ticks the gameplay advanced
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    pub fn is_frozen(&self) -> bool {
+        self.freeze_remaining > 0.
+    }
+
+    pub fn freeze(&mut self, duration: f32) {
+        self.freeze_remaining = self.freeze_remaining.max(duration);
+    }
+
+    pub fn advance(&mut self, delta: f32, is_paused: bool) {
+        self.delta = 0.;
+        if is_paused {
+            return;
+        }
+        if self.is_frozen() {
+            self.freeze_remaining = (self.freeze_remaining - delta).max(0.);
+            return;
+        }
+        self.delta = delta;
+        self.elapsed += delta;
+        self.tick += 1;
+    }
+}
+
+// The random number generator of the gameplay, it is seeded once at the
+// start so the seed of a game is known
+#[derive(Resource)]
+pub struct GameRng {
+    seed: u64,
+    rng: StdRng,
+}
+
+impl GameRng {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+}
+
+impl Default for GameRng {
+    fn default() -> Self {
+        Self::new(rand::random())
+    }
+}
+
+impl RngCore for GameRng {
+    fn next_u32(&mut self) -> u32 {
+        self.rng.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.rng.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.rng.fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.rng.try_fill_bytes(dest)
+    }
+}
+
+#[derive(Resource, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HighScore {
+    pub points: u32,
+    pub speed_percent: u32,
+}
+
+impl Default for HighScore {
+    fn default() -> Self {
+        Self {
+            points: 0,
+            speed_percent: 100,
+        }
+    }
+}
+
+// The fruit symbol of a round, shown in the fruit row of the HUD
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Fruit {
+    Cherry,
+    Strawberry,
+    Orange,
+    Apple,
+    Melon,
+    Galaxian,
+    Bell,
+    Key,
+}
+
+impl Fruit {
+    pub fn get_icon_id(&self) -> &'static str {
+        match self {
+            Fruit::Cherry => "icon.cherry",
+            Fruit::Strawberry => "icon.strawberry",
+            Fruit::Orange => "icon.orange",
+            Fruit::Apple => "icon.apple",
+            Fruit::Melon => "icon.melon",
+            Fruit::Galaxian => "icon.galaxian",
+            Fruit::Bell => "icon.bell",
+            Fruit::Key => "icon.key",
+        }
+    }
+}
+
+pub struct RoundSpec {
+    pub fruit: Fruit,
+    pub frightened_duration: f32,
+    pub frightened_blinks: u32,
+    pub elroy1_dots: u32,
+    pub elroy2_dots: u32,
+}
+
+impl RoundSpec {
+    pub fn get(round: u32) -> &'static Self {
+        let index = round.max(1) as usize - 1;
+        &ROUND_SPECS[index.min(ROUND_SPECS.len() - 1)]
+    }
+
+    pub fn has_frightened_time(&self) -> bool {
+        self.frightened_duration > 0.
+    }
+
+    // The blinks are placed at the end of the frightened time, this is the
+    // time since the first blink started
+    pub fn frightened_blink_elapsed(&self, elapsed_energized: f32) -> Option<f32> {
+        let remaining = self.frightened_duration - elapsed_energized;
+        let blink_time = self.frightened_blinks as f32 * FRIGHTENED_BLINK_DURATION;
+        if remaining <= 0. || remaining > blink_time {
+            return None;
+        }
+        Some(blink_time - remaining)
+    }
+
+    // Each blink shows the blink sprite for the first half of its duration
+    pub fn is_frightened_blink(&self, elapsed_energized: f32) -> bool {
+        self.frightened_blink_elapsed(elapsed_energized)
+            .is_some_and(|elapsed| elapsed % FRIGHTENED_BLINK_DURATION < FRIGHTENED_BLINK_DURATION / 2.)
+    }
+
+    pub fn elroy_stage(&self, remaining_dots: u32) -> u32 {
+        if remaining_dots <= self.elroy2_dots {
+            2
+        } else if remaining_dots <= self.elroy1_dots {
+            1
+        } else {
+            0
+        }
+    }
+}
+
+pub fn update_game_clock(
+    mut clock: ResMut<GameClock>,
+    state: Res<State<GameState>>,
+) {
+    clock.advance(SIMULATION_TICK, state.0 == GameState::Paused);
+}
+
+// The game speed changes the number of simulation ticks per second, each
+// tick stays the same
+pub fn apply_game_speed_to_simulation(
+    game: Res<Game>,
+    mut fixed_time: ResMut<FixedTime>,
+) {
+    let period = Duration::from_secs_f32(SIMULATION_TICK / game.speed());
+    if fixed_time.period != period {
+        fixed_time.period = period;
+    }
+}
+
+// The game speed setting is taken over until the first points are scored,
+// then it stays the same for the rest of the game so scores are comparable
+pub fn update_game_speed(
+    mut game: ResMut<Game>,
+    settings: Res<Settings>,
+) {
+    let speed_percent = settings.gameplay.speed_percent.clamp(GAME_SPEED_MIN, GAME_SPEED_MAX);
+    if game.points == 0 && game.speed_percent != speed_percent {
+        game.speed_percent = speed_percent;
+    }
+}
+
+pub fn freeze_on_ghost_eaten(
+    mut clock: ResMut<GameClock>,
+    mut ghost_events: EventReader<GhostEaten>,
+) {
+    if ghost_events.iter().count() > 0 {
+        clock.freeze(FREEZE_DURATION_GHOST_EATEN);
+    }
+}
+
+// Smoothly rises from 0 to 1 and falls back to 0 within each period, it
+// replaces flashes when reduced flashing is enabled
+pub fn fade_pulse(elapsed: f32, period: f32) -> f32 {
+    0.5 - 0.5 * (elapsed / period * std::f32::consts::TAU).cos()
+}
+
+// Add the points of everything pacman ate
+pub fn update_points(
+    mut game: ResMut<Game>,
+    mut dot_events: EventReader<DotEaten>,
+    mut energizer_events: EventReader<EnergizerEaten>,
+    mut ghost_events: EventReader<GhostEaten>,
+) {
+    game.points += dot_events.iter().count() as u32 * POINTS_DOT;
+    game.points += energizer_events.iter().count() as u32 * POINTS_ENERGIZER;
+    game.points += ghost_events.iter().map(|event| event.points).sum::<u32>();
+}
+
+pub fn save_high_score(game: Res<Game>, mut high_score: ResMut<HighScore>) {
+    if game.points > high_score.points {
+        high_score.points = game.points;
+        high_score.speed_percent = game.speed_percent;
+        storage::save(HIGH_SCORE_KEY, &*high_score);
+    }
 }
@@ -2,11 +2,19 @@
 // Daniel Bauer (bauerda@pm.me)
 //
 
+use std::collections::{HashMap, VecDeque};
+
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
+use super::animation::*;
+use super::atlas::*;
 use super::collision::*;
+use super::events::*;
 use super::game::*;
+use super::maze::*;
 use super::pacman::*;
+use super::settings::*;
 use super::states::*;
 use super::unit::*;
 
@@ -14,16 +22,30 @@ const GHOST_SPEED_NORMAL: f32 = 400.;
 const GHOST_SPEED_FRIGHTENED: f32 = 300.;
 const GHOST_SPEED_ROUND_INCREASE: f32 = 25.;
 const GHOST_SPEED_MAX: f32 = 500.;
+const GHOST_SPEED_ELROY_INCREASE: f32 = 25.;
+
+const GHOST_SPEED_MOVE_OUT: f32 = 333.;
+const GHOST_SPEED_EYES: f32 = 800.;
+
+// The ghosts leave and enter the house through the door, inside the house
+// they walk along the row above their start positions
+const GHOST_HOUSE_DOOR: UnitPosition = UnitPosition { x: 1380, y: 883 };
+const GHOST_HOUSE_ROW_Y: i32 = 713;
+
+// The eyes of eaten ghosts facing left, right, up and down
+const GHOST_EYES_SEQUENCES: [&str; 4] = ["ghost.eyes.left", "ghost.eyes.right", "ghost.eyes.up", "ghost.eyes.down"];
 
 pub struct GhostsPlugin;
 
 impl Plugin for GhostsPlugin {
     fn build(&self, app: &mut App) {
         app
-            .insert_resource(FixedTime::new_from_secs(0.003))
+            .init_resource::<Elroy>()
 
             // New Round State
             .add_systems((
+                reset_elroy
+                    .in_schedule(OnEnter(GameState::NewRound)),
                 despawn_ghosts
                     .in_schedule(OnEnter(GameState::NewRound)),
                 spawn_ghosts
@@ -31,15 +53,33 @@ impl Plugin for GhostsPlugin {
                     .after(despawn_ghosts),
             ))
 
-            // Running State
+            // Simulation Ticks
             .add_systems((
                 move_ghosts_out
-                    .in_schedule(CoreSchedule::FixedUpdate),
+                    .in_schedule(CoreSchedule::FixedUpdate)
+                    .in_set(SimulationSet::Movement),
+                return_eaten_ghosts
+                    .in_schedule(CoreSchedule::FixedUpdate)
+                    .in_set(SimulationSet::Movement)
+                    .run_if(in_state(GameState::Running)),
+                update_elroy
+                    .in_schedule(CoreSchedule::FixedUpdate)
+                    .in_set(SimulationSet::Movement)
+                    .run_if(in_state(GameState::Running))
+                    .before(ghosts_movement),
                 ghosts_movement
-                    .in_set(OnUpdate(GameState::Running)),
+                    .in_schedule(CoreSchedule::FixedUpdate)
+                    .in_set(SimulationSet::Movement)
+                    .run_if(in_state(GameState::Running)),
+                frighten_ghosts
+                    .in_schedule(CoreSchedule::FixedUpdate)
+                    .run_if(in_state(GameState::Running))
+                    .after(pacman_eats_energizer),
+            ))
+
+            .add_systems((
                 animate_ghosts,
-                ghost_eats_pacman
-                    .in_set(OnUpdate(GameState::Running)),
+                apply_ghost_accessibility,
             ))
 
             // Round Won State
@@ -58,6 +98,8 @@ impl Plugin for GhostsPlugin {
             .add_systems((
                 despawn_ghosts
                     .in_schedule(OnEnter(PacmanState::Dead)),
+                suspend_elroy
+                    .in_schedule(OnEnter(PacmanState::Dead)),
             ))
         ;
     }
@@ -85,14 +127,27 @@ impl GhostId {
         }
     }
 
-    pub fn get_sprite_index(&self) -> usize {
+    // The name of the ghost in sprite frame names
+    pub fn name(&self) -> &'static str {
         match self {
-            GhostId::Blinky => 0,
-            GhostId::Pinky => 2,
-            GhostId::Inky => 4,
-            GhostId::Clyde => 6,
-            GhostId::Frightened => 8,
-            GhostId::FrightenedBlink => 10,
+            GhostId::Blinky => "blinky",
+            GhostId::Pinky => "pinky",
+            GhostId::Inky => "inky",
+            GhostId::Clyde => "clyde",
+            GhostId::Frightened => "frightened",
+            GhostId::FrightenedBlink => "frightened_blink",
+        }
+    }
+
+    // The sprite sequences of the ghost facing left, right, up and down
+    pub fn sprite_sequences(&self) -> [&'static str; 4] {
+        match self {
+            GhostId::Blinky => ["ghost.blinky.left", "ghost.blinky.right", "ghost.blinky.up", "ghost.blinky.down"],
+            GhostId::Pinky => ["ghost.pinky.left", "ghost.pinky.right", "ghost.pinky.up", "ghost.pinky.down"],
+            GhostId::Inky => ["ghost.inky.left", "ghost.inky.right", "ghost.inky.up", "ghost.inky.down"],
+            GhostId::Clyde => ["ghost.clyde.left", "ghost.clyde.right", "ghost.clyde.up", "ghost.clyde.down"],
+            GhostId::Frightened => ["ghost.frightened"; 4],
+            GhostId::FrightenedBlink => ["ghost.frightened_blink"; 4],
         }
     }
 
@@ -101,6 +156,45 @@ impl GhostId {
     }
 }
 
+// The classic ghost colors or colors which can be told apart with color
+// vision deficiencies
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GhostPalette {
+    #[default]
+    Classic,
+    Colorblind,
+}
+
+impl GhostPalette {
+    pub fn label(self) -> &'static str {
+        match self {
+            GhostPalette::Classic => "Classic",
+            GhostPalette::Colorblind => "Colorblind",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            GhostPalette::Classic => GhostPalette::Colorblind,
+            GhostPalette::Colorblind => GhostPalette::Classic,
+        }
+    }
+
+    // The variant of the ghost atlas with the colors of the palette
+    fn atlas_variant(self) -> Option<&'static str> {
+        match self {
+            GhostPalette::Classic => None,
+            GhostPalette::Colorblind => Some("colorblind"),
+        }
+    }
+}
+
+// A shape above a ghost which tells the ghosts apart without colors
+#[derive(Component)]
+pub struct GhostMarker;
+
+// An eaten ghost is only eyes until it is back in the ghost house, where
+// it revives and moves out again
 #[derive(Component)]
 pub struct Ghost {
     pub ghost_id: GhostId,
@@ -108,47 +202,75 @@ pub struct Ghost {
     pub spawn_time: f32,
     pub is_moved_out: bool,
     pub is_frightened: bool,
-    pub movement_time: f32,
-    pub animation_time: f32,
+    pub is_eaten: bool,
+    pub movement_remainder: f32,
 }
 
 impl Ghost {
-    pub fn new(ghost_id: GhostId, spawn_time: f32) -> Self {
+    pub fn new(ghost_id: GhostId, spawn_time: f32, current_direction: UnitDirection) -> Self {
         Self {
             ghost_id,
-            current_direction: UnitDirection::random(),
+            current_direction,
             spawn_time,
             is_moved_out: false,
             is_frightened: false,
-            movement_time: 0.,
-            animation_time: 0.,
+            is_eaten: false,
+            movement_remainder: 0.,
         }
     }
 
     pub fn reset(&mut self, spawn_time: f32) {
         self.is_moved_out = false;
         self.is_frightened = false;
+        self.is_eaten = false;
         self.spawn_time = spawn_time;
     }
+
+    // The eyes of the eaten ghost are on their way to the ghost house door
+    pub fn is_returning_to_house(&self) -> bool {
+        self.is_eaten && self.is_moved_out
+    }
+
+    // The frightened ghosts look straight ahead, the others and the eyes
+    // of eaten ghosts look where the ghost is going
+    pub fn sprite_sequence(&self, is_frightened_blink: bool) -> &'static str {
+        let sequences = if self.is_frightened && is_frightened_blink {
+            GhostId::FrightenedBlink.sprite_sequences()
+        } else if self.is_frightened {
+            GhostId::Frightened.sprite_sequences()
+        } else if self.is_eaten {
+            GHOST_EYES_SEQUENCES
+        } else {
+            self.ghost_id.sprite_sequences()
+        };
+        self.current_direction.pick(sequences)
+    }
+
+    // Blinky as Cruise Elroy chases pacman unless he is frightened
+    pub fn is_elroy(&self, elroy: &Elroy) -> bool {
+        self.ghost_id == GhostId::Blinky && elroy.is_active() && !self.is_frightened
+    }
 }
 
-fn load_ghost_sprite(
-    ghost_id: GhostId,
-    asset_server: &Res<AssetServer>,
-    texture_atlases: &mut ResMut<Assets<TextureAtlas>>,
-) -> SpriteSheetBundle {
-    let texture_atlas = TextureAtlas::from_grid(
-        asset_server.load("sprites/ghosts.png"),
-        Vec2::new(UNIT_SIZE as f32, UNIT_SIZE as f32),
-        2,
-        6,
-        None,
-        None
-    );
+// Cruise Elroy stage of Blinky, which speeds him up and makes him chase
+// pacman when only a few dots are left in the round
+#[derive(Resource, Default)]
+pub struct Elroy {
+    pub stage: u32,
+    pub is_suspended: bool,
+}
+
+impl Elroy {
+    pub fn is_active(&self) -> bool {
+        self.stage > 0 && !self.is_suspended
+    }
+}
 
+// The markers are drawn above the head of the ghost, one shape per ghost
+fn ghost_marker_sprite(is_visible: bool) -> SpriteSheetBundle {
     SpriteSheetBundle {
-        texture_atlas: texture_atlases.add(texture_atlas),
-        sprite: TextureAtlasSprite::new(ghost_id.get_sprite_index()),
+        transform: Transform::from_xyz(0., 75., 1.).with_scale(Vec3::splat(0.6)),
+        visibility: if is_visible { Visibility::Inherited } else { Visibility::Hidden },
         ..default()
     }
 }
@@ -156,28 +278,44 @@ fn load_ghost_sprite(
 fn spawn_ghost(
     ghost_id: GhostId,
     commands: &mut Commands,
-    asset_server: &Res<AssetServer>,
-    texture_atlases: &mut ResMut<Assets<TextureAtlas>>,
-    time: &Res<Time>,
+    clock: &Res<GameClock>,
+    rng: &mut ResMut<GameRng>,
+    settings: &Res<Settings>,
 ) {
-    commands.spawn((
-        Ghost::new(ghost_id.clone(), time.elapsed_seconds() + 5. * ghost_id.get_id() as f32),
-        ghost_id.get_start_pos(),
-        UnitScale::square(0.95),
-        load_ghost_sprite(ghost_id, asset_server, texture_atlases),
-    ));
+    let accessibility = &settings.accessibility;
+    let ghost = Ghost::new(ghost_id, clock.elapsed_seconds() + 5. * ghost_id.get_id() as f32, UnitDirection::random(&mut **rng));
+    let sequence = ghost.sprite_sequence(false);
+    commands
+        .spawn((
+            ghost,
+            ghost_id.get_start_pos(),
+            UnitLastPosition(ghost_id.get_start_pos()),
+            UnitPath::default(),
+            UnitScale::square(0.95),
+            AtlasSprite::new(&format!("{}.0", sequence))
+                .with_variant(accessibility.ghost_palette.atlas_variant()),
+            SpriteAnimation::new("ghost_walk", sequence),
+            SpriteSheetBundle::default(),
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                GhostMarker,
+                AtlasSprite::new(&format!("marker.{}", ghost_id.name())),
+                ghost_marker_sprite(accessibility.is_ghost_markers),
+            ));
+        });
 }
 
 pub fn spawn_ghosts(
     mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
-    time: Res<Time>,
+    clock: Res<GameClock>,
+    mut rng: ResMut<GameRng>,
+    settings: Res<Settings>,
 ) {
-    spawn_ghost(GhostId::Blinky, &mut commands, &asset_server, &mut texture_atlases, &time);
-    spawn_ghost(GhostId::Pinky, &mut commands, &asset_server, &mut texture_atlases, &time);
-    spawn_ghost(GhostId::Inky, &mut commands, &asset_server, &mut texture_atlases, &time);
-    spawn_ghost(GhostId::Clyde, &mut commands, &asset_server, &mut texture_atlases, &time);
+    spawn_ghost(GhostId::Blinky, &mut commands, &clock, &mut rng, &settings);
+    spawn_ghost(GhostId::Pinky, &mut commands, &clock, &mut rng, &settings);
+    spawn_ghost(GhostId::Inky, &mut commands, &clock, &mut rng, &settings);
+    spawn_ghost(GhostId::Clyde, &mut commands, &clock, &mut rng, &settings);
 }
 
 pub fn despawn_ghosts(
@@ -185,127 +323,372 @@ pub fn despawn_ghosts(
     query_ghosts: Query<Entity, With<Ghost>>,
 ) {
     for entity in query_ghosts.iter() {
-        commands.entity(entity).despawn();
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+// Switch the ghost colors and markers when the accessibility settings change
+pub fn apply_ghost_accessibility(
+    settings: Res<Settings>,
+    mut query_ghosts: Query<&mut AtlasSprite, With<Ghost>>,
+    mut query_markers: Query<&mut Visibility, With<GhostMarker>>,
+    mut palette: Local<GhostPalette>,
+) {
+    if !settings.is_changed() { return; }
+
+    let accessibility = &settings.accessibility;
+    if *palette != accessibility.ghost_palette {
+        *palette = accessibility.ghost_palette;
+        for mut atlas_sprite in query_ghosts.iter_mut() {
+            atlas_sprite.variant = palette.atlas_variant().map(str::to_string);
+        }
+    }
+
+    let visibility = if accessibility.is_ghost_markers { Visibility::Inherited } else { Visibility::Hidden };
+    for mut marker_visibility in query_markers.iter_mut() {
+        if *marker_visibility != visibility {
+            *marker_visibility = visibility;
+        }
     }
 }
 
 pub fn ghosts_movement(
     game: Res<Game>,
-    mut query_ghosts: Query<(&mut Ghost, &mut UnitPosition)>,
-    game_state: Res<State<GameState>>,
-    time: Res<Time>,
+    elroy: Res<Elroy>,
+    mut query_ghosts: Query<(&mut Ghost, &mut UnitPosition, &mut UnitPath)>,
+    query_pacman: Query<&UnitPosition, (With<Pacman>, Without<Ghost>)>,
+    clock: Res<GameClock>,
+    mut rng: ResMut<GameRng>,
 ) {
-    if game_state.0 != GameState::Running { return; }
-
-    for (mut ghost, mut ghost_pos) in query_ghosts.iter_mut() {
-        // Do not move ghost if it is not moved out or has no direction
-        if !ghost.is_moved_out || ghost.current_direction == UnitDirection::None { continue; }
+    for (mut ghost, mut ghost_pos, mut ghost_path) in query_ghosts.iter_mut() {
+        // Do not move ghost if it is not moved out, only eyes or has no direction
+        if !ghost.is_moved_out || ghost.is_eaten || ghost.current_direction == UnitDirection::None { continue; }
 
         // Select a random next direction which is not the opposite of the current direction
-        let mut next_random_direction = UnitDirection::random();
+        let mut next_random_direction = UnitDirection::random(&mut *rng);
         while next_random_direction == ghost.current_direction.opposite() {
-            next_random_direction = UnitDirection::random();
+            next_random_direction = UnitDirection::random(&mut *rng);
         }
 
         // Select a random valid current direction if the last current direction is not valid anymore
         while !unit_can_move_in_direction(&ghost_pos, ghost.current_direction) {
-            ghost.current_direction = UnitDirection::random();
+            ghost.current_direction = UnitDirection::random(&mut *rng);
         }
 
+        let is_elroy = ghost.is_elroy(&elroy);
+
         // Calculate ghost speed
         let ghost_speed = if ghost.is_frightened {
             GHOST_SPEED_FRIGHTENED
         } else {
             let round_speed = GHOST_SPEED_NORMAL + game.round as f32 * GHOST_SPEED_ROUND_INCREASE;
-            if round_speed > GHOST_SPEED_MAX { GHOST_SPEED_MAX } else { round_speed }
+            let round_speed = if round_speed > GHOST_SPEED_MAX { GHOST_SPEED_MAX } else { round_speed };
+            if is_elroy { round_speed + elroy.stage as f32 * GHOST_SPEED_ELROY_INCREASE } else { round_speed }
         };
+        let pixel_speed = pixel_steps(ghost_speed, clock.delta_seconds(), &mut ghost.movement_remainder);
+
+        // Blinky as Cruise Elroy keeps chasing pacman instead of wandering around
+        if let (true, Some(pac_pos)) = (is_elroy, query_pacman.iter().next()) {
+            for _ in 0..pixel_speed {
+                // Decide where to go at intersections or when being blocked
+                if (ghost_pos.is_at_tile_center() && ghost_pos.tile().is_intersection())
+                    || !unit_can_move_in_direction(&ghost_pos, ghost.current_direction) {
+                    ghost.current_direction = chase_direction(&ghost_pos, ghost.current_direction, pac_pos);
+                }
+                ghost_path.step(&mut ghost_pos, ghost.current_direction);
+            }
+            continue;
+        }
 
         // Move ghost forward
-        let pixel_speed = (time.delta_seconds() * ghost_speed) as i32;
         for _ in 0..pixel_speed {
             if unit_can_move_in_direction(&ghost_pos, next_random_direction) {
                 // Try taking the next direction
                 ghost.current_direction = next_random_direction;
-                ghost_pos.move_in_direction(ghost.current_direction);
+                ghost_path.step(&mut ghost_pos, ghost.current_direction);
             } else if unit_can_move_in_direction(&ghost_pos, ghost.current_direction) {
                 // Else move forward in current direction
-                ghost_pos.move_in_direction(ghost.current_direction);
+                ghost_path.step(&mut ghost_pos, ghost.current_direction);
+            }
+        }
+    }
+}
+
+// Select the direction which brings the ghost closest to the target,
+// the ghost only turns around if there is no other way to go
+fn chase_direction(
+    pos: &UnitPosition,
+    current_direction: UnitDirection,
+    target: &UnitPosition,
+) -> UnitDirection {
+    let distance = |direction: UnitDirection| {
+        let mut new_pos = *pos;
+        new_pos.move_in_direction(direction);
+        let dx = (new_pos.x - target.x) as i64;
+        let dy = (new_pos.y - target.y) as i64;
+        dx * dx + dy * dy
+    };
+
+    UnitDirection::ALL
+        .into_iter()
+        .filter(|direction| *direction != current_direction.opposite())
+        .filter(|direction| unit_can_move_in_direction(pos, *direction))
+        .min_by_key(|direction| distance(*direction))
+        .unwrap_or(current_direction.opposite())
+}
+
+// The first direction of the shortest walk along the tile grid from the
+// tile to the target tile
+fn shortest_path_direction(from: TilePosition, target: TilePosition) -> Option<UnitDirection> {
+    let mut first_directions = HashMap::from([(from, UnitDirection::None)]);
+    let mut tiles = VecDeque::from([from]);
+    while let Some(tile) = tiles.pop_front() {
+        if tile == target {
+            return Some(first_directions[&tile]).filter(|direction| *direction != UnitDirection::None);
+        }
+        for (direction, neighbor) in tile.walkable_neighbors() {
+            if !neighbor.is_in_grid() || first_directions.contains_key(&neighbor) { continue; }
+            let first_direction = if tile == from { direction } else { first_directions[&tile] };
+            first_directions.insert(neighbor, first_direction);
+            tiles.push_back(neighbor);
+        }
+    }
+    None
+}
+
+// The eyes decide at tile centers which way leads to the ghost house door,
+// the door lies between the centers of two tiles
+fn eyes_direction(pos: &UnitPosition, current_direction: UnitDirection) -> UnitDirection {
+    let door_tile = GHOST_HOUSE_DOOR.tile();
+    let direction = if pos.tile() == door_tile && pos.y == GHOST_HOUSE_DOOR.y {
+        if pos.x < GHOST_HOUSE_DOOR.x { UnitDirection::Right } else { UnitDirection::Left }
+    } else if pos.is_at_tile_center() {
+        shortest_path_direction(pos.tile(), door_tile).unwrap_or(current_direction)
+    } else {
+        current_direction
+    };
+
+    if unit_can_move_in_direction(pos, direction) {
+        direction
+    } else {
+        chase_direction(pos, direction, &GHOST_HOUSE_DOOR)
+    }
+}
+
+// The tile centers a ghost passes next, a ghost chasing a target follows
+// it like in the movement, a wandering ghost may turn at any intersection
+// so its path ends at the next one
+pub fn planned_path(
+    pos: &UnitPosition,
+    direction: UnitDirection,
+    target: Option<&UnitPosition>,
+    max_tiles: usize,
+) -> Vec<UnitPosition> {
+    let mut pos = *pos;
+    let mut direction = direction;
+    let mut path = Vec::new();
+    let max_steps = max_tiles * TILE_SIZE.ceil() as usize;
+
+    for _ in 0..max_steps {
+        let is_at_intersection = pos.is_at_tile_center() && pos.tile().is_intersection();
+        if let Some(target) = target {
+            if is_at_intersection || !unit_can_move_in_direction(&pos, direction) {
+                direction = chase_direction(&pos, direction, target);
             }
+        } else if (is_at_intersection && !path.is_empty()) || !unit_can_move_in_direction(&pos, direction) {
+            break;
         }
+
+        pos.move_in_direction(direction);
+        if pos.is_at_tile_center() {
+            path.push(pos);
+            if path.len() >= max_tiles { break; }
+        }
+    }
+    path
+}
+
+pub fn update_elroy(
+    game: Res<Game>,
+    pellets: Res<Pellets>,
+    mut elroy: ResMut<Elroy>,
+    query_ghosts: Query<&Ghost>,
+) {
+    // After pacman died, Elroy is suspended until Clyde left the ghost house
+    if elroy.is_suspended && query_ghosts.iter().any(|ghost| ghost.ghost_id == GhostId::Clyde && ghost.is_moved_out) {
+        elroy.is_suspended = false;
+    }
+
+    let stage = game.round_spec().elroy_stage(pellets.remaining());
+    if elroy.stage != stage {
+        elroy.stage = stage;
+    }
+}
+
+pub fn frighten_ghosts(
+    game: Res<Game>,
+    mut energizer_events: EventReader<EnergizerEaten>,
+    mut query_ghosts: Query<&mut Ghost>,
+) {
+    if energizer_events.iter().count() == 0 { return; }
+
+    // No frightened time left in the later rounds
+    if !game.round_spec().has_frightened_time() { return; }
+
+    // The eyes of eaten ghosts are not frightened again
+    for mut ghost in query_ghosts.iter_mut().filter(|ghost| !ghost.is_eaten) {
+        ghost.is_frightened = true;
     }
 }
 
+pub fn suspend_elroy(mut elroy: ResMut<Elroy>) {
+    elroy.is_suspended = true;
+}
+
+pub fn reset_elroy(mut elroy: ResMut<Elroy>) {
+    *elroy = Elroy::default();
+}
+
 pub fn move_ghosts_out(
-    mut query_ghosts: Query<(&mut Ghost, &mut UnitPosition)>,
-    time: Res<Time>,
+    mut query_ghosts: Query<(&mut Ghost, &mut UnitPosition, &mut UnitPath)>,
+    clock: Res<GameClock>,
 ) {
-    for (mut ghost, mut ghost_pos) in query_ghosts.iter_mut() {
-        // Wait until the ghost is allowed to move out
-        if ghost.spawn_time > time.elapsed_seconds() {
+    for (mut ghost, mut ghost_pos, mut ghost_path) in query_ghosts.iter_mut() {
+        // Wait until the ghost is allowed to move out, the eyes of eaten
+        // ghosts enter the house first
+        if ghost.is_moved_out || ghost.is_eaten || ghost.spawn_time > clock.elapsed_seconds() {
             continue;
         }
 
         // Move the ghost out of the box
-        if !ghost.is_moved_out {
-            if ghost_pos.y < 713 {
-                ghost_pos.move_in_direction(UnitDirection::Up);
-            } else if ghost_pos.y >= 713 && ghost_pos.x > 1380 {
-                ghost_pos.move_in_direction(UnitDirection::Left);
-            } else if ghost_pos.y >= 713 && ghost_pos.x < 1380 {
-                ghost_pos.move_in_direction(UnitDirection::Right);
-            } else if ghost_pos.y < 883 {
-                ghost_pos.move_in_direction(UnitDirection::Up);
+        let pixel_speed = pixel_steps(GHOST_SPEED_MOVE_OUT, clock.delta_seconds(), &mut ghost.movement_remainder);
+        for _ in 0..pixel_speed {
+            let direction = if ghost_pos.y < GHOST_HOUSE_ROW_Y {
+                UnitDirection::Up
+            } else if ghost_pos.x > GHOST_HOUSE_DOOR.x {
+                UnitDirection::Left
+            } else if ghost_pos.x < GHOST_HOUSE_DOOR.x {
+                UnitDirection::Right
+            } else if ghost_pos.y < GHOST_HOUSE_DOOR.y {
+                UnitDirection::Up
             } else {
                 ghost.is_moved_out = true;
+                break;
+            };
+            ghost.current_direction = direction;
+            ghost_path.step(&mut ghost_pos, direction);
+        }
+    }
+}
+
+// The eyes of eaten ghosts take the shortest way to the ghost house door
+// and walk into the house to their start position, where the ghost
+// revives and moves out again right away
+pub fn return_eaten_ghosts(
+    mut query_ghosts: Query<(&mut Ghost, &mut UnitPosition, &mut UnitPath)>,
+    clock: Res<GameClock>,
+) {
+    for (mut ghost, mut ghost_pos, mut ghost_path) in query_ghosts.iter_mut() {
+        if !ghost.is_eaten { continue; }
+
+        let start_pos = ghost.ghost_id.get_start_pos();
+        let pixel_speed = pixel_steps(GHOST_SPEED_EYES, clock.delta_seconds(), &mut ghost.movement_remainder);
+        for _ in 0..pixel_speed {
+            if *ghost_pos == GHOST_HOUSE_DOOR {
+                ghost.is_moved_out = false;
             }
+
+            let direction = if ghost.is_moved_out {
+                eyes_direction(&ghost_pos, ghost.current_direction)
+            } else if ghost_pos.y > GHOST_HOUSE_ROW_Y {
+                UnitDirection::Down
+            } else if ghost_pos.x > start_pos.x {
+                UnitDirection::Left
+            } else if ghost_pos.x < start_pos.x {
+                UnitDirection::Right
+            } else if ghost_pos.y > start_pos.y {
+                UnitDirection::Down
+            } else {
+                ghost.reset(clock.elapsed_seconds());
+                break;
+            };
+            ghost.current_direction = direction;
+            ghost_path.step(&mut ghost_pos, direction);
         }
     }
 }
 
+// Select the sprite sequence of the ghost, the walk animation steps through it
 pub fn animate_ghosts(
-    mut query_ghosts: Query<(&mut Ghost, &mut TextureAtlasSprite)>,
+    game: Res<Game>,
+    settings: Res<Settings>,
+    mut query_ghosts: Query<(&Ghost, &mut SpriteAnimation, &mut TextureAtlasSprite)>,
     query_pacman: Query<&Pacman>,
-    time: Res<Time>,
+    clock: Res<GameClock>,
 ) {
-    for (mut ghost, mut sprite) in query_ghosts.iter_mut() {
-        // Animate sprite
-        let elapsed = time.elapsed_seconds() - ghost.animation_time;
-        if elapsed >= 0.5 {
-            if sprite.index % 2 == 0 { sprite.index += 1; } else { sprite.index -= 1; }
-            ghost.animation_time = time.elapsed_seconds();
-        }
-        let offset = sprite.index % 2;
-
-        // Frightened ghost sprite & blinking ghost
+    for (ghost, mut animation, mut sprite) in query_ghosts.iter_mut() {
+        // Blinking frightened ghosts, with reduced flashing the ghost fades
+        // out and in instead
+        let mut alpha = 1.;
+        let mut is_blink = false;
         if ghost.is_frightened {
             if let Some(pacman) = query_pacman.iter().next() {
-                let elapsed_energized = time.elapsed_seconds() - pacman.start_time_energized;
-                if (elapsed_energized >= 7.6 && elapsed_energized < 7.8)
-                    || (elapsed_energized >= 7.2 && elapsed_energized < 7.4)
-                    || (elapsed_energized >= 6.8 && elapsed_energized < 7.0)
-                    || (elapsed_energized >= 6.4 && elapsed_energized < 6.6)
-                    || (elapsed_energized >= 6.0 && elapsed_energized < 6.2) {
-                    sprite.index = GhostId::FrightenedBlink.get_sprite_index() + offset;
+                let elapsed_energized = clock.elapsed_seconds() - pacman.start_time_energized;
+                let round_spec = game.round_spec();
+                if settings.accessibility.is_reduced_flash {
+                    if let Some(elapsed_blink) = round_spec.frightened_blink_elapsed(elapsed_energized) {
+                        alpha = 1. - 0.5 * fade_pulse(elapsed_blink, 2. * FRIGHTENED_BLINK_DURATION);
+                    }
                 } else {
-                    sprite.index = GhostId::Frightened.get_sprite_index() + offset;
+                    is_blink = round_spec.is_frightened_blink(elapsed_energized);
                 }
             }
-        } else {
-            sprite.index = ghost.ghost_id.get_sprite_index() + offset;
+        }
+        let sequence = ghost.sprite_sequence(is_blink);
+        if animation.sequence != sequence {
+            animation.sequence = sequence;
+        }
+        if sprite.color.a() != alpha {
+            sprite.color.set_a(alpha);
         }
     }
 }
 
-pub fn ghost_eats_pacman(
-    mut next_pacman_state: ResMut<NextState<PacmanState>>,
-    mut query_pacman: Query<&UnitPosition, With<Pacman>>,
-    mut query_ghost: Query<(&mut Ghost, &UnitPosition)>,
-) {
-    if let Some(pac_pos) = query_pacman.iter_mut().next() {
-        for (ghost, ghost_pos) in query_ghost.iter_mut() {
-            if !ghost.is_frightened && units_collide(&pac_pos, UNIT_HITBOX_SIZE, &ghost_pos, UNIT_HITBOX_SIZE) {
-                next_pacman_state.set(PacmanState::Dead);
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LONG_TICK: f32 = 0.2;
+
+    #[test]
+    fn eyes_return_to_the_house_from_every_tile() {
+        let mut world = World::new();
+        let mut clock = GameClock::default();
+        clock.advance(LONG_TICK, false);
+        world.insert_resource(clock);
+        let mut schedule = Schedule::new();
+        schedule.add_systems((store_unit_last_positions, return_eaten_ghosts).chain());
+
+        let tiles = (0..TILE_ROWS)
+            .flat_map(|row| (0..TILE_COLUMNS).map(move |column| TilePosition { column, row }))
+            .filter(|tile| tile.is_walkable());
+        for tile in tiles {
+            let mut ghost = Ghost::new(GhostId::Inky, 0., UnitDirection::Left);
+            ghost.is_moved_out = true;
+            ghost.is_eaten = true;
+            let pos = tile.to_unit();
+            let entity = world.spawn((ghost, pos, UnitLastPosition(pos), UnitPath::default())).id();
+
+            for _ in 0..100 {
+                schedule.run(&mut world);
+                if !world.get::<Ghost>(entity).unwrap().is_eaten { break; }
             }
+
+            let ghost = world.get::<Ghost>(entity).unwrap();
+            assert!(!ghost.is_eaten && !ghost.is_moved_out, "eyes from {:?} did not return", tile);
+            assert_eq!(ghost.current_direction, UnitDirection::Down);
+            assert_eq!(world.get::<UnitPosition>(entity), Some(&GhostId::Inky.get_start_pos()));
+            world.despawn(entity);
         }
     }
 }
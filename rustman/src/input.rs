@@ -5,22 +5,28 @@
 use bevy::prelude::*;
 
 use super::pacman::*;
+use super::scaling::*;
 use super::states::*;
 use super::unit::*;
 
 const TOUCH_INPUT_SENSITIVITY: f32 = 30.;
+const TOUCH_PAD_BUTTON_SIZE: f32 = 48.;
+const TOUCH_PAD_COLOR: Color = Color::rgba(1., 0.92, 0., 0.35);
 
 pub struct InputPlugin;
 
 impl Plugin for InputPlugin {
     fn build(&self, app: &mut App) {
         app
+            .add_startup_system(setup_touch_pad)
             .add_systems((
+                update_touch_pad_layout,
+                touch_pad_input
+                    .in_set(OnUpdate(GameState::Running)),
                 pause_input,
                 pacman_movement_input
                     .in_set(OnUpdate(GameState::Running))
-                    .after(pacman_movement_input_touch)
-                    .before(pacman_movement),
+                    .after(pacman_movement_input_touch),
                 pacman_movement_input_touch
                     .in_set(OnUpdate(GameState::Running))
                     .before(pacman_movement_input),
@@ -44,42 +50,134 @@ pub fn pause_input(
     }
 }
 
+// The directions of the inputs are given on the screen, which is rotated
+// against the maze in portrait windows
 pub fn pacman_movement_input(
     keys: Res<Input<KeyCode>>,
+    scaling: Res<MazeScaling>,
     mut query_pacman: Query<&mut Pacman>
 ) {
     if let Some(mut pac) = query_pacman.iter_mut().next() {  
         if keys.pressed(KeyCode::Left) {
-            pac.next_direction = UnitDirection::Left
+            pac.next_direction = scaling.maze_direction(UnitDirection::Left)
         } else if keys.pressed(KeyCode::Down) {
-            pac.next_direction = UnitDirection::Down
+            pac.next_direction = scaling.maze_direction(UnitDirection::Down)
         } else if keys.pressed(KeyCode::Up) {
-            pac.next_direction = UnitDirection::Up
+            pac.next_direction = scaling.maze_direction(UnitDirection::Up)
         } else if keys.pressed(KeyCode::Right) {
-            pac.next_direction = UnitDirection::Right
+            pac.next_direction = scaling.maze_direction(UnitDirection::Right)
         };
     }
 }
 
 pub fn pacman_movement_input_touch(
     touches: Res<Touches>,
+    scaling: Res<MazeScaling>,
     mut query_pacman: Query<&mut Pacman>
 ) {
     if let Some(mut pac) = query_pacman.iter_mut().next() { 
         for finger in touches.iter() {
             if finger.start_position().x > finger.position().x
                 && finger.start_position().x - finger.position().x > TOUCH_INPUT_SENSITIVITY {
-                pac.next_direction = UnitDirection::Left;
+                pac.next_direction = scaling.maze_direction(UnitDirection::Left);
             } else if finger.start_position().x < finger.position().x
                 && finger.position().x - finger.start_position().x > TOUCH_INPUT_SENSITIVITY {
-                pac.next_direction = UnitDirection::Right;
+                pac.next_direction = scaling.maze_direction(UnitDirection::Right);
             } else if finger.start_position().y > finger.position().y
                 && finger.start_position().y - finger.position().y > TOUCH_INPUT_SENSITIVITY {
-                pac.next_direction = UnitDirection::Up;
+                pac.next_direction = scaling.maze_direction(UnitDirection::Up);
             } else if finger.start_position().y < finger.position().y
                 && finger.position().y - finger.start_position().y > TOUCH_INPUT_SENSITIVITY {
-                pac.next_direction = UnitDirection::Down;
+                pac.next_direction = scaling.maze_direction(UnitDirection::Down);
             }
         }
     }
 }
+
+// A direction pad in the free space below the maze in portrait windows
+#[derive(Component)]
+struct TouchPad;
+
+#[derive(Component)]
+struct TouchPadButton(UnitDirection);
+
+fn setup_touch_pad(mut commands: Commands) {
+    // The pad is laid out in three rows with the buttons in a cross
+    let rows = [
+        [None, Some(UnitDirection::Up), None],
+        [Some(UnitDirection::Left), None, Some(UnitDirection::Right)],
+        [None, Some(UnitDirection::Down), None],
+    ];
+    let cell_style = Style {
+        size: Size::new(Val::Px(TOUCH_PAD_BUTTON_SIZE), Val::Px(TOUCH_PAD_BUTTON_SIZE)),
+        ..default()
+    };
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    display: Display::None,
+                    position_type: PositionType::Absolute,
+                    position: UiRect { left: Val::Px(0.), bottom: Val::Px(0.), ..default() },
+                    size: Size::new(Val::Percent(100.), Val::Px(TOUCH_CONTROLS_HEIGHT)),
+                    flex_direction: FlexDirection::Column,
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                ..default()
+            },
+            TouchPad,
+        ))
+        .with_children(|pad| {
+            for row in rows {
+                pad
+                    .spawn(NodeBundle::default())
+                    .with_children(|parent| {
+                        for direction in row {
+                            match direction {
+                                Some(direction) => {
+                                    parent.spawn((
+                                        ButtonBundle {
+                                            style: cell_style.clone(),
+                                            background_color: TOUCH_PAD_COLOR.into(),
+                                            ..default()
+                                        },
+                                        TouchPadButton(direction),
+                                    ));
+                                }
+                                None => {
+                                    parent.spawn(NodeBundle { style: cell_style.clone(), ..default() });
+                                }
+                            }
+                        }
+                    });
+            }
+        });
+}
+
+fn update_touch_pad_layout(
+    scaling: Res<MazeScaling>,
+    mut query_pad: Query<&mut Style, With<TouchPad>>,
+) {
+    if !scaling.is_changed() { return; }
+
+    let display = if scaling.controls_height() > 0. { Display::Flex } else { Display::None };
+    for mut style in query_pad.iter_mut() {
+        style.display = display;
+    }
+}
+
+fn touch_pad_input(
+    scaling: Res<MazeScaling>,
+    query_buttons: Query<(&Interaction, &TouchPadButton), Changed<Interaction>>,
+    mut query_pacman: Query<&mut Pacman>,
+) {
+    let Some(mut pac) = query_pacman.iter_mut().next() else { return };
+    for (interaction, button) in query_buttons.iter() {
+        if *interaction == Interaction::Clicked {
+            pac.next_direction = scaling.maze_direction(button.0);
+        }
+    }
+}
@@ -0,0 +1,180 @@
+//
+// Daniel Bauer (bauerda@pm.me)
+//
+
+use bevy::prelude::*;
+
+use super::packs::*;
+use super::states::*;
+
+const LOADING_BAR_WIDTH: f32 = 400.;
+const LOADING_BAR_HEIGHT: f32 = 12.;
+const LOADING_BAR_BORDER: f32 = 2.;
+const LOADING_BAR_COLOR: Color = Color::YELLOW;
+
+// The error screen can not rely on the fonts of the asset pack, which may
+// be the files that failed, so its font is built into the game
+const LOADING_ERROR_FONT: &[u8] = include_bytes!("../assets/fonts/FiraMono-Medium.ttf");
+const LOADING_ERROR_FONT_PATH: &str = "fonts/FiraMono-Medium.ttf";
+const LOADING_ERROR_FONT_SIZE: f32 = 20.;
+
+pub struct LoadingPlugin;
+
+impl Plugin for LoadingPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems((
+                spawn_loading_screen
+                    .in_schedule(OnEnter(GameState::Loading)),
+                update_loading_bar
+                    .in_set(OnUpdate(GameState::Loading)),
+                despawn_loading_screen
+                    .in_schedule(OnExit(GameState::Loading)),
+                spawn_loading_error
+                    .in_schedule(OnEnter(GameState::LoadingFailed)),
+            ))
+        ;
+    }
+}
+
+#[derive(Component)]
+struct LoadingScreen;
+
+#[derive(Component)]
+struct LoadingBar;
+
+// The loading screen only shows a progress bar, its fonts may not be
+// loaded yet
+fn spawn_loading_screen(mut commands: Commands) {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                ..default()
+            },
+            LoadingScreen,
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(LOADING_BAR_WIDTH), Val::Px(LOADING_BAR_HEIGHT)),
+                        border: UiRect::all(Val::Px(LOADING_BAR_BORDER)),
+                        ..default()
+                    },
+                    background_color: LOADING_BAR_COLOR.into(),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    // The black inside of the frame is covered from the left
+                    parent
+                        .spawn(NodeBundle {
+                            style: Style {
+                                size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                                ..default()
+                            },
+                            background_color: Color::BLACK.into(),
+                            ..default()
+                        })
+                        .with_children(|parent| {
+                            parent.spawn((
+                                NodeBundle {
+                                    style: Style {
+                                        size: Size::new(Val::Percent(0.), Val::Percent(100.)),
+                                        ..default()
+                                    },
+                                    background_color: LOADING_BAR_COLOR.into(),
+                                    ..default()
+                                },
+                                LoadingBar,
+                            ));
+                        });
+                });
+        });
+}
+
+fn update_loading_bar(
+    pack_assets: Res<PackAssets>,
+    asset_server: Res<AssetServer>,
+    mut query_bar: Query<&mut Style, With<LoadingBar>>,
+) {
+    let width = Val::Percent(pack_assets.progress(&asset_server) * 100.);
+    for mut style in query_bar.iter_mut() {
+        if style.size.width != width {
+            style.size.width = width;
+        }
+    }
+}
+
+fn despawn_loading_screen(
+    mut commands: Commands,
+    query_screen: Query<Entity, With<LoadingScreen>>,
+) {
+    for entity in query_screen.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+// List the files which could not be loaded, another pack can still be
+// picked in the settings for the next start
+fn spawn_loading_error(
+    mut commands: Commands,
+    pack_assets: Res<PackAssets>,
+    asset_server: Res<AssetServer>,
+    mut fonts: ResMut<Assets<Font>>,
+) {
+    let font = match Font::try_from_bytes(LOADING_ERROR_FONT.to_vec()) {
+        Ok(font) => fonts.add(font),
+        Err(error) => {
+            warn!("Could not read the built-in font: {}", error);
+            asset_server.load(LOADING_ERROR_FONT_PATH)
+        }
+    };
+    let text_style = TextStyle {
+        font,
+        font_size: LOADING_ERROR_FONT_SIZE,
+        color: Color::GRAY,
+    };
+    let mut sections = vec![TextSection::new(
+        "Could not load the game\n\n",
+        TextStyle {
+            color: Color::YELLOW,
+            ..text_style.clone()
+        },
+    )];
+    sections.extend(pack_assets.failed().iter().map(|path| TextSection::new(
+        format!("{}\n", path),
+        TextStyle {
+            color: Color::WHITE,
+            ..text_style.clone()
+        },
+    )));
+    sections.push(TextSection::new(
+        format!("\nCheck the files of the asset pack {} or pick\nanother pack in the settings (Esc) and restart.", pack_assets.pack()),
+        text_style,
+    ));
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                ..default()
+            },
+            LoadingScreen,
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_sections(sections));
+        });
+}
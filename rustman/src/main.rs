@@ -2,62 +2,95 @@
 // Daniel Bauer (bauerda@pm.me)
 //
 
+mod animation;
+mod atlas;
 mod collision;
+mod debug;
+mod events;
 mod states;
 mod game;
 mod ghosts;
 mod input;
+mod loading;
 mod maze;
+mod music;
+mod packs;
 mod pacman;
+mod popups;
 mod scaling;
+mod settings;
 mod sound;
+mod storage;
+mod synth;
 mod ui;
 mod unit;
 
 use bevy::{
     prelude::*,
+    audio::AudioPlugin,
     window::PresentMode,
     render::color::Color,
 };
 
+use animation::SpriteAnimationPlugin;
+use atlas::SpriteAtlasPlugin;
+use debug::DebugPlugin;
+use events::EventsPlugin;
 use game::GamePlugin;
 use ghosts::GhostsPlugin;
+use loading::LoadingPlugin;
 use maze::MazePlugin;
+use music::MusicPlugin;
+use packs::AssetPackPlugin;
 use pacman::PacmanPlugin;
+use popups::PopupsPlugin;
 use scaling::ScalingPlugin;
+use settings::SettingsPlugin;
 use sound::SoundPlugin;
 use states::StatesPlugin;
 use ui::UiPlugin;
 use input::InputPlugin;
 
 fn main() {
+    let mut default_plugins = DefaultPlugins
+        .set(WindowPlugin {
+            primary_window: Some(Window {
+                title: "Rustman".into(),
+                resolution: (1120., 480.).into(),
+                present_mode: PresentMode::AutoVsync,
+                fit_canvas_to_parent: true,
+                prevent_default_event_handling: false,
+                ..default()
+            }),
+            ..default()})
+        .set(ImagePlugin::default_nearest());
+
+    // Run without sound if it is disabled on the command line, without an
+    // audio output device the audio plugin plays nothing by itself
+    if std::env::args().any(|arg| arg == "--no-audio") {
+        default_plugins = default_plugins.disable::<AudioPlugin>();
+    }
+
     App::new()
         .insert_resource(ClearColor(Color::BLACK))
-        .add_startup_system(setup_camera)
-        .add_plugins(DefaultPlugins
-            .set(WindowPlugin {
-                primary_window: Some(Window {
-                    title: "Rustman".into(),
-                    resolution: (1120., 480.).into(),
-                    present_mode: PresentMode::AutoVsync,
-                    fit_canvas_to_parent: true,
-                    prevent_default_event_handling: false,
-                    ..default()
-                }),
-                ..default()})
-            .set(ImagePlugin::default_nearest()))
+        .add_plugins(default_plugins)
+        .add_plugin(SettingsPlugin)
         .add_plugin(StatesPlugin)
+        .add_plugin(AssetPackPlugin)
+        .add_plugin(LoadingPlugin)
+        .add_plugin(EventsPlugin)
         .add_plugin(GamePlugin)
+        .add_plugin(SpriteAtlasPlugin)
+        .add_plugin(SpriteAnimationPlugin)
         .add_plugin(MazePlugin)
         .add_plugin(PacmanPlugin)
         .add_plugin(GhostsPlugin)
+        .add_plugin(PopupsPlugin)
         .add_plugin(InputPlugin)
         .add_plugin(ScalingPlugin)
         .add_plugin(SoundPlugin)
+        .add_plugin(MusicPlugin)
         .add_plugin(UiPlugin)
+        .add_plugin(DebugPlugin)
         .run();
 }
-
-fn setup_camera(mut commands: Commands) {
-    commands.spawn(Camera2dBundle::default());
-}
@@ -5,180 +5,295 @@
 use bevy::prelude::*;
 use bevy::sprite::MaterialMesh2dBundle;
 
+use super::animation::*;
+use super::atlas::*;
 use super::collision::*;
 use super::game::*;
+use super::settings::*;
 use super::states::*;
 use super::unit::*;
 
 pub const MAZE_WIDTH: u32 = 3700;
 pub const MAZE_HEIGHT: u32 = 1233;
 
-const DOTS_HORIZONTAL: u32 = 52;
-const DOTS_VERTICAL: u32 = 15;
-
-const DOTS_START_X_Y: f32 = 150.;
-const DOTS_SPACING: f32 = 66.66;
+// The maze is laid out on a grid of tiles, the center of the first tile
+// and the tile size are given in maze pixels
+pub const TILE_COLUMNS: i32 = 52;
+pub const TILE_ROWS: i32 = 15;
+pub const TILE_ORIGIN_X_Y: f32 = 150.;
+pub const TILE_SIZE: f32 = 200. / 3.;
 
 const DOT_COLOR: Color = Color::rgba(1., 0.666, 0.643, 1.);
+const DOT_COLOR_HIGH_CONTRAST: Color = Color::YELLOW;
 
 const DOT_RADIUS: f32 = 10.;
 const ENERGIZER_RADIUS: f32 = 30.;
 
+// With reduced flashing the maze fades out and in once instead of the
+// blinks of the maze_blink clip, the round won state lasts just as long
+const MAZE_FADE_DURATION: f32 = 3.;
+const MAZE_FADE_START: f32 = 1.4;
+const MAZE_FADE_PERIOD: f32 = 1.4;
+
 pub struct MazePlugin;
 
 impl Plugin for MazePlugin {
     fn build(&self, app: &mut App) {
         app
+            .init_resource::<Pellets>()
             .add_startup_systems((
                 setup_maze,
+                setup_pellet_assets,
             ))
             .add_systems((
                 spawn_dots_and_energizers
                     .in_schedule(OnEnter(GameState::NewRound)),
+                start_maze_blink
+                    .in_schedule(OnEnter(GameState::RoundWon)),
                 blink_maze
                     .in_set(OnUpdate(GameState::RoundWon)),
+                apply_maze_accessibility,
             ))
         ;
     }
 }
 
-#[derive(Component)]
-pub struct Dot;
-
-#[derive(Component)]
-pub struct Energizer;
+#[derive(Component, Clone, Copy, PartialEq, Eq)]
+pub enum Pellet {
+    Dot,
+    Energizer,
+}
 
 #[derive(Component)]
 pub struct Maze;
 
-fn load_maze_sprite(
-    asset_server: Res<AssetServer>,
-    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
-) -> SpriteSheetBundle {
-    let texture_atlas = TextureAtlas::from_grid(
-        asset_server.load("sprites/maze.png"),
-        Vec2::new(MAZE_WIDTH as f32, MAZE_HEIGHT as f32),
-        1,
-        2,
-        None,
-        None
-    );
-
-    SpriteSheetBundle {
-        texture_atlas: texture_atlases.add(texture_atlas),
-        sprite: TextureAtlasSprite::new(0),
-        ..default()
+// Shared mesh and material handles for rendering the pellets
+#[derive(Resource)]
+pub struct PelletAssets {
+    dot_mesh: Handle<Mesh>,
+    energizer_mesh: Handle<Mesh>,
+    material: Handle<ColorMaterial>,
+}
+
+// The dots and energizers of the current round indexed by their tile,
+// each tile holds the pellet and the entity rendering it
+#[derive(Resource)]
+pub struct Pellets {
+    tiles: Vec<Option<(Pellet, Entity)>>,
+    remaining: u32,
+    total: u32,
+}
+
+impl Default for Pellets {
+    fn default() -> Self {
+        Self {
+            tiles: vec![None; (TILE_COLUMNS * TILE_ROWS) as usize],
+            remaining: 0,
+            total: 0,
+        }
     }
 }
 
-pub fn setup_maze(
-    mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    texture_atlases: ResMut<Assets<TextureAtlas>>,
-) {
+impl Pellets {
+    pub fn remaining(&self) -> u32 {
+        self.remaining
+    }
+
+    pub fn total(&self) -> u32 {
+        self.total
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.remaining == 0
+    }
+
+    pub fn eaten_fraction(&self) -> f32 {
+        if self.total == 0 { 0. } else { 1. - self.remaining as f32 / self.total as f32 }
+    }
+
+    // Remove the pellet of the given kind which collides with a unit at the
+    // given position, only the tile of the position has to be checked
+    pub fn eat(&mut self, pos: &UnitPosition, pellet: Pellet) -> Option<Entity> {
+        let tile = pos.tile();
+        if !tile.is_in_grid() {
+            return None;
+        }
+
+        let index = Self::index(tile);
+        match self.tiles[index] {
+            Some((tile_pellet, entity)) if tile_pellet == pellet
+                && units_collide(pos, UNIT_HITBOX_SIZE, &tile.to_unit(), UNIT_HITBOX_SIZE) => {
+                self.tiles[index] = None;
+                self.remaining -= 1;
+                Some(entity)
+            }
+            _ => None,
+        }
+    }
+
+    fn insert(&mut self, tile: TilePosition, pellet: Pellet, entity: Entity) {
+        self.tiles[Self::index(tile)] = Some((pellet, entity));
+        self.remaining += 1;
+        self.total += 1;
+    }
+
+    fn clear(&mut self) -> impl Iterator<Item = Entity> + '_ {
+        self.remaining = 0;
+        self.total = 0;
+        self.tiles.iter_mut().filter_map(|tile| tile.take().map(|(_, entity)| entity))
+    }
+
+    fn index(tile: TilePosition) -> usize {
+        (tile.row * TILE_COLUMNS + tile.column) as usize
+    }
+}
+
+pub fn setup_maze(mut commands: Commands) {
     commands.spawn((
         Maze,
-        load_maze_sprite(asset_server, texture_atlases),
+        AtlasSprite::new("maze.0"),
+        SpriteSheetBundle::default(),
         UnitPosition { x: (MAZE_WIDTH / 2) as i32, y: (MAZE_HEIGHT / 2) as i32 },
         UnitScale::square(1.)
     ));
 }
 
-pub fn spawn_dots_and_energizers(
+pub fn setup_pellet_assets(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
 ) {
-    for i in 0..DOTS_VERTICAL {
-        for j in 0..DOTS_HORIZONTAL {
+    commands.insert_resource(PelletAssets {
+        dot_mesh: meshes.add(shape::Circle::new(DOT_RADIUS).into()),
+        energizer_mesh: meshes.add(shape::Circle::new(ENERGIZER_RADIUS).into()),
+        material: materials.add(ColorMaterial::from(DOT_COLOR)),
+    });
+}
+
+pub fn spawn_dots_and_energizers(
+    mut commands: Commands,
+    mut pellets: ResMut<Pellets>,
+    pellet_assets: Res<PelletAssets>,
+) {
+    // Remove pellets which may be left over from the last round
+    for entity in pellets.clear() {
+        commands.entity(entity).despawn();
+    }
+
+    for i in 0..TILE_ROWS {
+        for j in 0..TILE_COLUMNS {
             // Skip some positions where we do not want to have points
             if (i == 0 && (j == 18 || j == 19 ))
                 || (i == 7 && (j <= 3 || j >= 48))
-                || ((i >= 5 && i <= 10) && (j >= 14 && j <= 23))
-                || (i == 11 && (j >= 15 && j <= 23))
-                || ((i >= 4 && i <= 13) && j == 23) {
+                || ((5..=10).contains(&i) && (14..=23).contains(&j))
+                || (i == 11 && (15..=23).contains(&j))
+                || ((4..=13).contains(&i) && j == 23) {
                 continue;
             }
 
-            // Calculate the coords to spawn the point
-            let x = (DOTS_START_X_Y + j as f32 * DOTS_SPACING) as i32;
-            let y = (DOTS_START_X_Y + i as f32 * DOTS_SPACING) as i32;
-
             // Spawn the point if it don't collide with obstacles
-            if !check_for_collisions(x as i32, y as i32, UNIT_HITBOX_SIZE) {
-                
+            let tile = TilePosition { column: j, row: i };
+            let pos = tile.to_unit();
+            if !check_for_collisions(pos.x, pos.y, UNIT_HITBOX_SIZE) {
+
                 // On the map we have 5 energizers
-                if (i == 0 && j == 0)
-                    || (i == 0 && j == 51)
-                    || (i == 8 && j == 27)
-                    || (i == 12 && j == 0)
-                    || (i == 12 && j == 51) {
-                    spawn_energizer(&mut commands, &mut meshes, &mut materials, x, y);
+                let pellet = if ((i == 0 || i == 12) && (j == 0 || j == 51))
+                    || (i == 8 && j == 27) {
+                    Pellet::Energizer
                 } else {
-                    spawn_dot(&mut commands, &mut meshes, &mut materials, x, y);
-                }
+                    Pellet::Dot
+                };
+
+                let entity = spawn_pellet(&mut commands, &pellet_assets, pellet, pos);
+                pellets.insert(tile, pellet, entity);
             }
         }
     }
 }
 
-fn spawn_dot(
+fn spawn_pellet(
     commands: &mut Commands,
-    meshes: &mut ResMut<Assets<Mesh>>,
-    materials: &mut ResMut<Assets<ColorMaterial>>,
-    x: i32,
-    y: i32
-) {
+    pellet_assets: &PelletAssets,
+    pellet: Pellet,
+    pos: UnitPosition,
+) -> Entity {
+    let mesh = match pellet {
+        Pellet::Dot => pellet_assets.dot_mesh.clone(),
+        Pellet::Energizer => pellet_assets.energizer_mesh.clone(),
+    };
+
     commands.spawn((
-        Dot,
+        pellet,
         MaterialMesh2dBundle {
-            mesh: meshes.add(shape::Circle::new(DOT_RADIUS).into()).into(),
-            material: materials.add(ColorMaterial::from(DOT_COLOR)),
+            mesh: mesh.into(),
+            material: pellet_assets.material.clone(),
             ..default()
         },
-        UnitPosition { x, y },
+        pos,
         UnitScale::square(1.)
-    ));
+    )).id()
 }
 
-fn spawn_energizer(
-    commands: &mut Commands,
-    meshes: &mut ResMut<Assets<Mesh>>,
-    materials: &mut ResMut<Assets<ColorMaterial>>,
-    x: i32,
-    y: i32
+// Switch the maze and pellet colors when the high contrast setting changes
+pub fn apply_maze_accessibility(
+    settings: Res<Settings>,
+    pellet_assets: Res<PelletAssets>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut query_maze: Query<&mut AtlasSprite, With<Maze>>,
+    mut is_high_contrast: Local<bool>,
 ) {
-    commands.spawn((
-        Energizer,
-        MaterialMesh2dBundle {
-            mesh: meshes.add(shape::Circle::new(ENERGIZER_RADIUS).into()).into(),
-            material: materials.add(ColorMaterial::from(DOT_COLOR)),
-            ..default()
-        },
-        UnitPosition { x, y },
-        UnitScale::square(1.)
-    ));
+    if !settings.is_changed() || *is_high_contrast == settings.accessibility.is_high_contrast { return; }
+    *is_high_contrast = settings.accessibility.is_high_contrast;
+
+    for mut atlas_sprite in query_maze.iter_mut() {
+        atlas_sprite.variant = is_high_contrast.then(|| "high_contrast".to_string());
+    }
+    if let Some(material) = materials.get_mut(&pellet_assets.material) {
+        material.color = if *is_high_contrast { DOT_COLOR_HIGH_CONTRAST } else { DOT_COLOR };
+    }
 }
 
+// The maze blinks after the round is won, with reduced flashing it fades
+// out and in once instead
+pub fn start_maze_blink(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    query_maze: Query<Entity, With<Maze>>,
+) {
+    if settings.accessibility.is_reduced_flash { return; }
+
+    for entity in query_maze.iter() {
+        commands.entity(entity).insert(SpriteAnimation::new("maze_blink", "maze"));
+    }
+}
+
+// The blinks end with their clip, the fade is timed by the game clock since
+// the round was won
 pub fn blink_maze(
+    mut commands: Commands,
     game: Res<Game>,
-    mut query_maze: Query<&mut TextureAtlasSprite, With<Maze>>,
+    clock: Res<GameClock>,
+    mut finished_events: EventReader<AnimationFinished>,
+    mut query_maze: Query<(Entity, &mut TextureAtlasSprite, &mut AtlasSprite, Option<&SpriteAnimation>), With<Maze>>,
     mut next_game_state: ResMut<NextState<GameState>>,
-    time: Res<Time>,
 ) {
-    if let Some(mut sprite) = query_maze.iter_mut().next() {
-
-        let elapsed = time.elapsed_seconds() - game.elapsed_time_state;
-
-        if elapsed >= 3. {
-            next_game_state.set(GameState::NewRound);
-        } else if (elapsed >= 1.4 && elapsed < 1.6)
-            || (elapsed >= 1.8 && elapsed < 2.)
-            || (elapsed >= 2.2 && elapsed < 2.4)
-            || (elapsed >= 2.6 && elapsed < 2.8) {
-            sprite.index = 1
-        } else {
-            sprite.index = 0
-        }
+    let Some((entity, mut sprite, mut atlas_sprite, animation)) = query_maze.iter_mut().next() else { return };
+
+    let elapsed = clock.elapsed_seconds() - game.elapsed_time_state;
+    let is_finished = match animation {
+        Some(animation) => finished_events.iter().any(|event| event.entity == entity && event.clip == animation.clip()),
+        None => elapsed >= MAZE_FADE_DURATION,
+    };
+
+    if is_finished {
+        commands.entity(entity).remove::<SpriteAnimation>();
+        atlas_sprite.frame = "maze.0".to_string();
+        sprite.color = Color::WHITE;
+        next_game_state.set(GameState::NewRound);
+    } else if animation.is_none() {
+        let fade_elapsed = elapsed - MAZE_FADE_START;
+        let fade = if (0. ..MAZE_FADE_PERIOD).contains(&fade_elapsed) { fade_pulse(fade_elapsed, MAZE_FADE_PERIOD) } else { 0. };
+        let brightness = 1. - 0.6 * fade;
+        sprite.color = Color::rgb(brightness, brightness, brightness);
     }
 }
@@ -0,0 +1,200 @@
+//
+// Daniel Bauer (bauerda@pm.me)
+//
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::game::*;
+use super::ghosts::*;
+use super::maze::*;
+use super::pacman::*;
+use super::settings::*;
+use super::sound::*;
+use super::states::*;
+use super::unit::*;
+
+const MUSIC_FADE_DURATION: f32 = 1.;
+const MUSIC_MAX_INTENSITY: u32 = 2;
+
+// Ghosts closer to pacman than this raise the intensity
+const MUSIC_GHOST_DISTANCE: i32 = 500;
+
+pub struct MusicPlugin;
+
+impl Plugin for MusicPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<AdaptiveMusic>()
+
+            // Running State
+            .add_systems((
+                play_adaptive_music
+                    .in_set(OnUpdate(GameState::Running)),
+                stop_adaptive_music
+                    .in_schedule(OnExit(GameState::Running)),
+            ))
+
+            // Pacman States
+            .add_systems((
+                stop_adaptive_music
+                    .in_schedule(OnEnter(PacmanState::Dead)),
+            ))
+        ;
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MusicMode {
+    #[default]
+    Classic,
+    Adaptive,
+}
+
+impl MusicMode {
+    pub fn label(self) -> &'static str {
+        match self {
+            MusicMode::Classic => "Classic",
+            MusicMode::Adaptive => "Adaptive",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            MusicMode::Classic => MusicMode::Adaptive,
+            MusicMode::Adaptive => MusicMode::Classic,
+        }
+    }
+}
+
+// The layers of the adaptive music, all stems loop with the same length
+// and are started together, so they only have to be faded in and out
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MusicStem {
+    Bass,
+    Drums,
+    Lead,
+    Chase,
+}
+
+const MUSIC_STEMS: [MusicStem; 4] = [
+    MusicStem::Bass,
+    MusicStem::Drums,
+    MusicStem::Lead,
+    MusicStem::Chase,
+];
+
+impl MusicStem {
+    pub fn name(self) -> &'static str {
+        match self {
+            MusicStem::Bass => "bass",
+            MusicStem::Drums => "drums",
+            MusicStem::Lead => "lead",
+            MusicStem::Chase => "chase",
+        }
+    }
+
+    // The bass always plays, the drums and the lead are added with rising
+    // intensity, while pacman is energized the chase replaces the lead
+    fn is_audible(self, intensity: u32, is_energized: bool) -> bool {
+        match self {
+            MusicStem::Bass => true,
+            MusicStem::Drums => intensity >= 1 || is_energized,
+            MusicStem::Lead => intensity >= 2 && !is_energized,
+            MusicStem::Chase => is_energized,
+        }
+    }
+}
+
+struct MusicLayer {
+    stem: MusicStem,
+    sink: SoundSink,
+    volume: f32,
+}
+
+#[derive(Resource, Default)]
+pub struct AdaptiveMusic {
+    pub intensity: u32,
+    layers: Vec<MusicLayer>,
+}
+
+// The intensity rises with the ghosts close to pacman, the dots eaten in
+// the round and when pacman is on his last life
+fn music_intensity(near_ghosts: usize, eaten_fraction: f32, lifes: u32) -> u32 {
+    let mut intensity = near_ghosts.min(MUSIC_MAX_INTENSITY as usize) as u32;
+    if eaten_fraction > 0.5 {
+        intensity += 1;
+    }
+    if lifes <= 1 {
+        intensity += 1;
+    }
+    intensity.min(MUSIC_MAX_INTENSITY)
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn play_adaptive_music(
+    game: Res<Game>,
+    pellets: Res<Pellets>,
+    settings: Res<Settings>,
+    pacman_state: Res<State<PacmanState>>,
+    query_pacman: Query<&UnitPosition, With<Pacman>>,
+    query_ghosts: Query<(&Ghost, &UnitPosition), Without<Pacman>>,
+    mut music: ResMut<AdaptiveMusic>,
+    mut sounds: Sounds,
+    time: Res<Time>,
+) {
+    if settings.audio.music_mode != MusicMode::Adaptive || pacman_state.0 == PacmanState::Dead {
+        if !music.layers.is_empty() {
+            music.layers.clear();
+            sounds.stop(SoundChannel::Music);
+        }
+        return;
+    }
+
+    let Some(pac_pos) = query_pacman.iter().next() else { return };
+    let near_ghosts = query_ghosts
+        .iter()
+        .filter(|(ghost, _)| ghost.is_moved_out && !ghost.is_frightened && !ghost.is_eaten)
+        .filter(|(_, pos)| (pos.x - pac_pos.x).abs() + (pos.y - pac_pos.y).abs() < MUSIC_GHOST_DISTANCE)
+        .count();
+    music.intensity = music_intensity(near_ghosts, pellets.eaten_fraction(), game.lifes);
+    let is_energized = pacman_state.0 == PacmanState::Energized;
+
+    // Start all stems together so they stay in sync
+    if music.layers.is_empty() {
+        music.layers = MUSIC_STEMS
+            .iter()
+            .map(|stem| MusicLayer {
+                stem: *stem,
+                sink: sounds.play_with_settings(
+                    SoundChannel::Music,
+                    SoundId::Music(*stem),
+                    PlaybackSettings::LOOP.with_volume(0.),
+                ),
+                volume: 0.,
+            })
+            .collect();
+    }
+
+    // Fade the stems in and out
+    let step = time.delta_seconds() / MUSIC_FADE_DURATION;
+    let intensity = music.intensity;
+    for layer in music.layers.iter_mut() {
+        layer.volume = if layer.stem.is_audible(intensity, is_energized) {
+            (layer.volume + step).min(1.)
+        } else {
+            (layer.volume - step).max(0.)
+        };
+        sounds.set_volume(SoundChannel::Music, &layer.sink, layer.volume);
+    }
+}
+
+pub fn stop_adaptive_music(
+    mut music: ResMut<AdaptiveMusic>,
+    mut sounds: Sounds,
+) {
+    if !music.layers.is_empty() {
+        music.layers.clear();
+        sounds.stop(SoundChannel::Music);
+    }
+}
@@ -0,0 +1,237 @@
+//
+// Daniel Bauer (bauerda@pm.me)
+//
+
+use bevy::asset::{Asset, AssetLoader, LoadContext, LoadState, LoadedAsset};
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use bevy::utils::{BoxedFuture, HashMap};
+use serde::Deserialize;
+
+use super::settings::*;
+use super::states::*;
+
+pub const DEFAULT_ASSET_PACK: &str = "default";
+
+// The packs which come with the game and can be picked in the settings,
+// other packs in the packs folder can be selected with --pack <name>
+pub const ASSET_PACKS: [&str; 2] = ["default", "mono"];
+
+pub struct AssetPackPlugin;
+
+impl Plugin for AssetPackPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_asset::<AssetPack>()
+            .init_asset_loader::<AssetPackLoader>()
+            .init_resource::<PackAssets>()
+            .add_startup_system(load_asset_packs)
+            .add_systems((
+                preload_pack_assets,
+                finish_loading
+                    .after(preload_pack_assets),
+            ).in_set(OnUpdate(GameState::Loading)))
+        ;
+    }
+}
+
+// A manifest which maps the logical ids of the assets to their files, e.g.
+// "font.hud" to "fonts/RustmanArcade.ttf". A pack only needs to list the
+// assets it replaces, the others are taken from the default pack.
+#[derive(Deserialize, TypeUuid)]
+#[uuid = "c4d81f3a-6e27-4b95-9a0c-2f7e5b16d849"]
+pub struct AssetPack {
+    pub name: String,
+    pub assets: HashMap<String, String>,
+}
+
+#[derive(Default)]
+pub struct AssetPackLoader;
+
+impl AssetLoader for AssetPackLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let pack: AssetPack = ron::de::from_bytes(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(pack));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["pack.ron"]
+    }
+}
+
+// The manifests of the default and the selected pack
+#[derive(Resource)]
+struct AssetPackHandles {
+    default: Handle<AssetPack>,
+    selected: Option<Handle<AssetPack>>,
+}
+
+// The files of the logical asset ids in the selected pack, all of them are
+// loaded before the game starts and kept loaded, so no asset is fetched
+// while playing
+#[derive(Resource, Default)]
+pub struct PackAssets {
+    pack: String,
+    paths: HashMap<String, String>,
+    handles: Vec<HandleUntyped>,
+    failed: Vec<String>,
+    is_resolved: bool,
+}
+
+impl PackAssets {
+    // The name of the pack the assets were loaded from, another pack picked
+    // in the settings is used from the next start on
+    pub fn pack(&self) -> &str {
+        &self.pack
+    }
+
+    pub fn is_resolved(&self) -> bool {
+        self.is_resolved
+    }
+
+    // Fraction of the assets which are loaded, for the loading screen
+    pub fn progress(&self, asset_server: &AssetServer) -> f32 {
+        if !self.is_resolved || self.handles.is_empty() {
+            return 0.;
+        }
+        let loaded = self.handles
+            .iter()
+            .filter(|handle| asset_server.get_load_state(*handle) == LoadState::Loaded)
+            .count();
+        loaded as f32 / self.handles.len() as f32
+    }
+
+    // The files which could not be loaded, the game does not start then
+    pub fn failed(&self) -> &[String] {
+        &self.failed
+    }
+
+    pub fn path(&self, id: &str) -> Option<&str> {
+        self.paths.get(id).map(String::as_str)
+    }
+
+    pub fn load<T: Asset>(&self, asset_server: &AssetServer, id: &str) -> Handle<T> {
+        match self.path(id) {
+            Some(path) => asset_server.load(path),
+            None => {
+                warn!("Asset {} is not defined in any asset pack", id);
+                Handle::default()
+            }
+        }
+    }
+
+    // Load an asset with the assets of the pack, e.g. the images named in
+    // an atlas description, the game starts when it is loaded
+    pub fn preload(&mut self, handle: HandleUntyped) {
+        self.handles.push(handle);
+    }
+}
+
+fn pack_path(pack: &str) -> String {
+    format!("packs/{}.pack.ron", pack)
+}
+
+// The pack given on the command line is used instead of the one in the
+// settings
+fn selected_pack(settings: &Settings) -> String {
+    let args: Vec<String> = std::env::args().collect();
+    args.iter()
+        .position(|arg| arg == "--pack")
+        .and_then(|index| args.get(index + 1))
+        .cloned()
+        .unwrap_or_else(|| settings.assets.pack.clone())
+}
+
+fn load_asset_packs(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    settings: Res<Settings>,
+    mut pack_assets: ResMut<PackAssets>,
+) {
+    let pack = selected_pack(&settings);
+    commands.insert_resource(AssetPackHandles {
+        default: asset_server.load(pack_path(DEFAULT_ASSET_PACK)),
+        selected: (pack != DEFAULT_ASSET_PACK).then(|| asset_server.load(pack_path(&pack))),
+    });
+    pack_assets.pack = pack;
+}
+
+// Resolve the files of the ids once the manifests are loaded and start
+// loading them. A selected pack which could not be loaded is skipped.
+fn preload_pack_assets(
+    pack_handles: Res<AssetPackHandles>,
+    packs: Res<Assets<AssetPack>>,
+    asset_server: Res<AssetServer>,
+    audio: Option<Res<Audio>>,
+    mut pack_assets: ResMut<PackAssets>,
+) {
+    if pack_assets.is_resolved { return; }
+
+    let is_loading = |handle: &Handle<AssetPack>| {
+        matches!(asset_server.get_load_state(handle), LoadState::NotLoaded | LoadState::Loading)
+    };
+    if is_loading(&pack_handles.default) || pack_handles.selected.as_ref().is_some_and(is_loading) {
+        return;
+    }
+
+    let mut paths = HashMap::default();
+    match packs.get(&pack_handles.default) {
+        Some(pack) => paths.extend(pack.assets.clone()),
+        None => pack_assets.failed.push(pack_path(DEFAULT_ASSET_PACK)),
+    }
+    if let Some(handle) = &pack_handles.selected {
+        match packs.get(handle) {
+            Some(pack) => {
+                info!("Using the asset pack {}", pack.name);
+                paths.extend(pack.assets.clone());
+            }
+            None => warn!("Could not load the asset pack {}, the default pack is used", pack_assets.pack),
+        }
+    }
+
+    // Sounds can only be loaded along with the audio plugin
+    for (id, path) in paths.iter() {
+        if id.starts_with("sound") && audio.is_none() { continue; }
+        pack_assets.handles.push(asset_server.load_untyped(path.as_str()));
+    }
+    pack_assets.paths = paths;
+    pack_assets.is_resolved = true;
+}
+
+// Start the game once all assets are loaded, if any asset failed to load
+// the game does not start and the failed files are shown instead
+pub fn finish_loading(
+    mut pack_assets: ResMut<PackAssets>,
+    asset_server: Res<AssetServer>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if !pack_assets.is_resolved { return; }
+
+    let mut failed = Vec::new();
+    for handle in pack_assets.handles.iter() {
+        match asset_server.get_load_state(handle) {
+            LoadState::NotLoaded | LoadState::Loading => return,
+            LoadState::Failed => failed.extend(
+                asset_server.get_handle_path(handle).map(|path| path.path().display().to_string()),
+            ),
+            _ => {}
+        }
+    }
+    pack_assets.failed.extend(failed);
+
+    if pack_assets.failed.is_empty() {
+        next_state.set(GameState::Start);
+    } else {
+        for path in pack_assets.failed.iter() {
+            error!("Could not load the asset {}", path);
+        }
+        next_state.set(GameState::LoadingFailed);
+    }
+}
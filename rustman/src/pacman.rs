@@ -4,11 +4,13 @@
 
 use bevy::prelude::*;
 
+use super::animation::*;
+use super::atlas::*;
 use super::collision::*;
+use super::events::*;
 use super::game::*;
 use super::ghosts::*;
 use super::maze::*;
-use super::sound::*;
 use super::states::*;
 use super::unit::*;
 
@@ -30,15 +32,33 @@ impl Plugin for PacmanPlugin {
                     .after(despawn_pacman),
             ))
 
-            // Running State
+            // Simulation Ticks
             .add_systems((
+                store_unit_last_positions
+                    .in_schedule(CoreSchedule::FixedUpdate)
+                    .in_set(SimulationSet::Clock)
+                    .after(update_game_clock),
                 pacman_movement
-                    .in_set(OnUpdate(GameState::Running)),
+                    .in_schedule(CoreSchedule::FixedUpdate)
+                    .in_set(SimulationSet::Movement)
+                    .run_if(in_state(GameState::Running)),
                 pacman_eats_dot
-                    .in_set(OnUpdate(GameState::Running)),
+                    .in_schedule(CoreSchedule::FixedUpdate)
+                    .in_set(SimulationSet::Contacts)
+                    .run_if(in_state(GameState::Running)),
                 pacman_eats_energizer
-                    .in_set(OnUpdate(GameState::Running)),
-                pacman_eats_ghost
+                    .in_schedule(CoreSchedule::FixedUpdate)
+                    .in_set(SimulationSet::Contacts)
+                    .run_if(in_state(GameState::Running)),
+                pacman_touches_ghosts
+                    .in_schedule(CoreSchedule::FixedUpdate)
+                    .in_set(SimulationSet::Contacts)
+                    .run_if(in_state(GameState::Running)),
+            ))
+
+            // Running State
+            .add_systems((
+                hide_pacman_while_frozen
                     .in_set(OnUpdate(GameState::Running)),
             ))
 
@@ -68,9 +88,8 @@ impl Plugin for PacmanPlugin {
 pub struct Pacman {
     pub current_direction: UnitDirection,
     pub next_direction: UnitDirection,
-    pub eaten_points: u32,
     pub eaten_ghosts: u32,
-    pub animation_time: f32,
+    pub movement_remainder: f32,
     pub start_time_energized: f32,
 }
 
@@ -79,9 +98,8 @@ impl Pacman {
         Self {
             current_direction: UnitDirection::Left,
             next_direction: UnitDirection::Left,
-            eaten_points: 0,
             eaten_ghosts: 0,
-            animation_time: 0.,
+            movement_remainder: 0.,
             start_time_energized: 0.,
         }
     }
@@ -90,47 +108,23 @@ impl Pacman {
 #[derive(Component)]
 pub struct PacmanDeathAnimation {
     pub start_animation_time: f32,
-    pub animation_time: f32,
-    pub played_sound: bool,
-}
-
-fn load_pacman_death_sprite(
-    asset_server: Res<AssetServer>,
-    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
-) -> SpriteSheetBundle {
-    let texture_atlas = TextureAtlas::from_grid(
-        asset_server.load("sprites/pacman_death.png"),
-        Vec2::new(UNIT_SIZE as f32, UNIT_SIZE as f32),
-        11,
-        1,
-        None,
-        None
-    );
-
-    SpriteSheetBundle {
-        texture_atlas: texture_atlases.add(texture_atlas),
-        sprite: TextureAtlasSprite::new(0),
-        ..default()
-    }
 }
 
 pub fn spawn_pacman_death_animation(
     mut commands: Commands,
     mut query_pacman: Query<&UnitPosition, With<Pacman>>,
-    asset_server: Res<AssetServer>,
-    texture_atlases: ResMut<Assets<TextureAtlas>>,
-    time: Res<Time>
+    clock: Res<GameClock>,
 ) {
     if let Some(pac_pos) = query_pacman.iter_mut().next() {
         commands.spawn((
             PacmanDeathAnimation {
-                start_animation_time: time.elapsed_seconds(),
-                animation_time: 0.,
-                played_sound: false,
+                start_animation_time: clock.elapsed_seconds(),
             },
-            pac_pos.clone(),
+            SpriteAnimation::new("pacman_death", "pacman.death"),
+            AtlasSprite::new("pacman.death.0"),
+            *pac_pos,
             UnitScale::square(0.95),
-            load_pacman_death_sprite(asset_server, texture_atlases),
+            SpriteSheetBundle::default(),
         ));
     }
 }
@@ -144,37 +138,19 @@ pub fn despawn_pacman_death_animation(
     }
 }
 
-fn load_pacman_sprite(
-    asset_server: Res<AssetServer>,
-    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
-) -> SpriteSheetBundle {
-    let texture_atlas = TextureAtlas::from_grid(
-        asset_server.load("sprites/pacman.png"),
-        Vec2::new(UNIT_SIZE as f32, UNIT_SIZE as f32),
-        3,
-        1,
-        None,
-        None
-    );
-
-    SpriteSheetBundle {
-        texture_atlas: texture_atlases.add(texture_atlas),
-        sprite: TextureAtlasSprite::new(0),
-        ..default()
-    }
-}
-
 pub fn spawn_pacman(
     mut commands: Commands,
     mut next_pacman_state: ResMut<NextState<PacmanState>>,
-    asset_server: Res<AssetServer>,
-    texture_atlases: ResMut<Assets<TextureAtlas>>,
 ) {
     commands.spawn((
         Pacman::new(),
         UnitPosition { x: PACMAN_START_X, y: PACMAN_START_Y },
+        UnitLastPosition(UnitPosition { x: PACMAN_START_X, y: PACMAN_START_Y }),
+        UnitPath::default(),
         UnitScale::square(0.95),
-        load_pacman_sprite(asset_server, texture_atlases),
+        SpriteAnimation::new("pacman_chomp", "pacman.left").paused(),
+        AtlasSprite::new("pacman.left.0"),
+        SpriteSheetBundle::default(),
     ));
 
     next_pacman_state.set(PacmanState::Normal);
@@ -190,175 +166,299 @@ pub fn despawn_pacman(
 }
 
 pub fn pacman_movement(
-    state: Res<State<GameState>>,
-    mut query_pacman: Query<(&mut Pacman, &mut UnitPosition, &mut TextureAtlasSprite, &mut Transform)>,
-    time: Res<Time>,
+    mut query_pacman: Query<(&mut Pacman, &mut UnitPosition, &mut UnitPath, &mut SpriteAnimation)>,
+    clock: Res<GameClock>,
 ) {
-    fn animate(
-        pacman: &mut Mut<Pacman>,
-        sprite: &mut Mut<TextureAtlasSprite>,
-        transform: &mut Mut<Transform>,
-        elapsed_startup: f32,
-    ) {
-        let elapsed = elapsed_startup - pacman.animation_time;
-        if elapsed < 0.05 { return; }
-        sprite.index = if sprite.index == 2 { 0 } else { sprite.index + 1 };
-
-        transform.rotation = Quat::from_rotation_z(
-            match pacman.current_direction {
-                UnitDirection::Up => f32::to_radians(270.0),
-                UnitDirection::Right => f32::to_radians(180.0),
-                UnitDirection::Down => f32::to_radians(90.0),
-                _ => f32::to_radians(0.)
-        });
-        
-        pacman.animation_time = elapsed_startup;
-    }
-
-    if state.0 != GameState::Running { return; }
-
     if let Some((
         mut pacman,
         mut pos,
-        mut sprite,
-        mut transform,
+        mut path,
+        mut animation,
     )) = query_pacman.iter_mut().next() {
-        let pixel_speed = (time.delta_seconds() * PACMAN_SPEED )as i32;
+        let last_pos = *pos;
+        let pixel_speed = pixel_steps(PACMAN_SPEED, clock.delta_seconds(), &mut pacman.movement_remainder);
         for _ in 0..pixel_speed {
             if unit_can_move_in_direction(&pos, pacman.next_direction) {
                 pacman.current_direction = pacman.next_direction;
             } else if !unit_can_move_in_direction(&pos, pacman.current_direction) {
                 break;
             }
-            pos.move_in_direction(pacman.current_direction);
-            animate(&mut pacman, &mut sprite, &mut transform, time.elapsed_seconds());
+            path.step(&mut pos, pacman.current_direction);
+        }
+
+        // Pacman only chomps while he moves and faces where he goes
+        let is_moving = *pos != last_pos;
+        if animation.is_paused == is_moving {
+            animation.is_paused = !is_moving;
+        }
+        let sequence = pacman.current_direction.pick(["pacman.left", "pacman.right", "pacman.up", "pacman.down"]);
+        if is_moving && animation.sequence != sequence {
+            animation.sequence = sequence;
         }
     }
 }
 
 fn pacman_eats_dot(
     mut commands: Commands,
-    mut game: ResMut<Game>,
-    mut query_pacman: Query<(&mut Pacman, &UnitPosition)>,
-    query_dot: Query<(Entity, &UnitPosition), With<Dot>>,
-    asset_server: Res<AssetServer>,
-    audio: Res<Audio>,
+    mut pellets: ResMut<Pellets>,
+    mut dot_events: EventWriter<DotEaten>,
+    query_pacman: Query<&UnitPosition, With<Pacman>>,
 ) {
-    if let Some((mut pac, pac_pos)) = query_pacman.iter_mut().next() {
-        for (dot_entity, dot_pos) in query_dot.iter() {
-            if units_collide(&pac_pos, UNIT_HITBOX_SIZE, &dot_pos, UNIT_HITBOX_SIZE) {
-                // Add game points
-                game.points += POINTS_DOT;
-                
-                // Despawn dot
-                commands.entity(dot_entity).despawn();
-
-                // Play eat sound
-                pac.eaten_points += 1;
-                audio.play(asset_server.load(
-                    if pac.eaten_points % 2 == 0 { "sounds/eat2.ogg"} else { "sounds/eat.ogg" } ));
-            }
+    if let Some(pac_pos) = query_pacman.iter().next() {
+        if let Some(dot_entity) = pellets.eat(pac_pos, Pellet::Dot) {
+            commands.entity(dot_entity).despawn();
+            dot_events.send(DotEaten { position: *pac_pos });
         }
     }
 }
 
 pub fn pacman_eats_energizer(
     mut commands: Commands,
-    mut game: ResMut<Game>,
+    game: Res<Game>,
+    mut pellets: ResMut<Pellets>,
+    mut energizer_events: EventWriter<EnergizerEaten>,
     mut query_pacman: Query<(&mut Pacman, &UnitPosition)>,
-    mut query_ghosts: Query<&mut Ghost>,
-    query_energizer: Query<(Entity, &UnitPosition), With<Energizer>>,
     mut next_pacman_state: ResMut<NextState<PacmanState>>,
-    asset_server: Res<AssetServer>,
-    audio: Res<Audio>,
-    time: Res<Time>,
+    clock: Res<GameClock>,
 ) {
     if let Some((mut pac, pac_pos)) = query_pacman.iter_mut().next() {
-        for (energizer_entity, energizer_pos) in query_energizer.iter() {
-            if units_collide(&pac_pos, UNIT_HITBOX_SIZE, &energizer_pos, UNIT_HITBOX_SIZE) {
-                // Add game points
-                game.points += POINTS_ENERGIZER;
-
-                // Despawn energizer
-                commands.entity(energizer_entity).despawn();
-
-                // Play eat sound
-                pac.eaten_points += 1;
-                audio.play(asset_server.load(
-                    if pac.eaten_points % 2 == 0 { "sounds/eat2.ogg"} else { "sounds/eat.ogg" } ));
-                
-                // Mark pacman energized
-                pac.eaten_ghosts = 0;
-                pac.start_time_energized = time.elapsed_seconds();
-                next_pacman_state.set(PacmanState::Energized);
-
-                // Set elapsed time to immediately start playing sound
-                game.elapsed_time_sound = time.elapsed_seconds() - SOUND_DURATION_AMBIENT_FRIGHT;
-
-                // Mark ghosts as frightened
-                for mut ghost in query_ghosts.iter_mut() {
-                    ghost.is_frightened = true;
-                }
+        if let Some(energizer_entity) = pellets.eat(pac_pos, Pellet::Energizer) {
+            commands.entity(energizer_entity).despawn();
+            energizer_events.send(EnergizerEaten { position: *pac_pos });
+
+            // No frightened time left in the later rounds
+            if !game.round_spec().has_frightened_time() {
+                return;
             }
+
+            // Mark pacman energized, the ghosts get frightened by the event
+            pac.eaten_ghosts = 0;
+            pac.start_time_energized = clock.elapsed_seconds();
+            next_pacman_state.set(PacmanState::Energized);
         }
     }
 }
 
-pub fn pacman_eats_ghost(
-    mut game: ResMut<Game>,
-    mut query_pacman: Query<(&mut Pacman, &UnitPosition)>,
-    mut query_ghost: Query<(&mut Ghost, &mut UnitPosition), Without<Pacman>>,
-    asset_server: Res<AssetServer>,
-    audio: Res<Audio>,
-    time: Res<Time>,
+// Check the paths pacman and the ghosts moved along during this tick for
+// contacts, so fast units can not pass through each other.
+// Contacts are resolved in the order they happened, pacman eats frightened
+// ghosts until he runs into a ghost which is not frightened. The eyes of
+// eaten ghosts pass through pacman.
+pub fn pacman_touches_ghosts(
+    mut next_pacman_state: ResMut<NextState<PacmanState>>,
+    mut ghost_events: EventWriter<GhostEaten>,
+    mut died_events: EventWriter<PacmanDied>,
+    mut query_pacman: Query<(&mut Pacman, &UnitPosition, &UnitPath)>,
+    mut query_ghost: Query<(Entity, &mut Ghost, &UnitPosition, &UnitPath), Without<Pacman>>,
 ) {
-    if let Some((mut pacman, pac_pos)) = query_pacman.iter_mut().next() {
-        for (mut ghost, mut ghost_pos) in query_ghost.iter_mut() {
-            if ghost.is_frightened && units_collide(&pac_pos, UNIT_HITBOX_SIZE, &ghost_pos, UNIT_HITBOX_SIZE) {
-                // Play eat ghost sound
-                audio.play(asset_server.load("sounds/eat_ghost.ogg"));
-                
-                // Reset ghost
-                *ghost_pos = ghost.ghost_id.get_start_pos();
-                ghost.reset(time.elapsed_seconds() + 5.);
-                
-                // Calculate points
-                pacman.eaten_ghosts += 1;
-                let mut points = POINTS_GHOST;                
-                for _ in 0..pacman.eaten_ghosts {
-                    points *= 2;
-                }
-                game.points += points;
+    if let Some((mut pacman, pac_pos, pac_path)) = query_pacman.iter_mut().next() {
+        let mut contacts: Vec<(f32, GhostId, Entity)> = query_ghost
+            .iter()
+            .filter(|(_, ghost, _, _)| !ghost.is_eaten)
+            .filter_map(|(entity, ghost, ghost_pos, ghost_path)| {
+                units_collide_along_paths(
+                    pac_path.positions(pac_pos), UNIT_HITBOX_SIZE,
+                    ghost_path.positions(ghost_pos), UNIT_HITBOX_SIZE,
+                ).map(|time_of_contact| (time_of_contact, ghost.ghost_id, entity))
+            })
+            .collect();
+
+        // Simultaneous contacts are resolved in the order of the ghosts
+        contacts.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.get_id().cmp(&b.1.get_id())));
+
+        for (_, _, entity) in contacts {
+            let Ok((_, mut ghost, ghost_pos, _)) = query_ghost.get_mut(entity) else { continue };
+
+            if !ghost.is_frightened {
+                next_pacman_state.set(PacmanState::Dead);
+                died_events.send(PacmanDied { position: *pac_pos });
+                break;
+            }
+
+            // The eyes of the ghost return to the ghost house from here
+            ghost.is_frightened = false;
+            ghost.is_eaten = true;
+
+            // Calculate points
+            pacman.eaten_ghosts += 1;
+            let mut points = POINTS_GHOST;
+            for _ in 0..pacman.eaten_ghosts {
+                points *= 2;
             }
+            ghost_events.send(GhostEaten { ghost: ghost.ghost_id, points, position: *ghost_pos });
         }
     }
 }
 
-pub fn pacman_death_animation(
-    mut next_pacman_state: ResMut<NextState<PacmanState>>,
-    mut query_pacman: Query<(&mut PacmanDeathAnimation, &mut TextureAtlasSprite)>,
-    asset_server: Res<AssetServer>,
-    audio: Res<Audio>,
-    time: Res<Time>,
+// Pacman is hidden while the gameplay is frozen after eating a ghost
+pub fn hide_pacman_while_frozen(
+    clock: Res<GameClock>,
+    mut query_pacman: Query<&mut Visibility, With<Pacman>>,
 ) {
-    if let Some((mut pac, mut sprite)) = query_pacman.iter_mut().next() {
-        // Play pacman death sound
-        if !pac.played_sound {
-            audio.play(asset_server.load("sounds/death.ogg"));
-            pac.played_sound = true;
+    let visibility = if clock.is_frozen() { Visibility::Hidden } else { Visibility::Inherited };
+    for mut pacman_visibility in query_pacman.iter_mut() {
+        if *pacman_visibility != visibility {
+            *pacman_visibility = visibility;
         }
+    }
+}
 
-        // Animate sprite every 0.15 seconds
-        let elapsed_since_last_call = time.elapsed_seconds() - pac.animation_time;
-        if elapsed_since_last_call > 0.15 && sprite.index < 10 {
-            sprite.index += 1;
-            pac.animation_time = time.elapsed_seconds();
-        }
-        
-        // Change pacman state after 2 seconds to signal ready for respawn
-        let elapsed_since_start = time.elapsed_seconds() - pac.start_animation_time;
+pub fn pacman_death_animation(
+    mut next_pacman_state: ResMut<NextState<PacmanState>>,
+    query_pacman: Query<&PacmanDeathAnimation>,
+    clock: Res<GameClock>,
+) {
+    // Change pacman state after 2 seconds to signal ready for respawn
+    if let Some(pac) = query_pacman.iter().next() {
+        let elapsed_since_start = clock.elapsed_seconds() - pac.start_animation_time;
         if elapsed_since_start >= 2.0 {
             next_pacman_state.set(PacmanState::Respawn);
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::event::Events;
+
+    use super::*;
+
+    // Pacman walks left along the top corridor, a tick this long moves him
+    // 90 pixels, far more than the hitboxes
+    const LONG_TICK: f32 = 0.2;
+    const CORRIDOR_Y: i32 = 1083;
+
+    fn setup_world() -> World {
+        let mut world = World::new();
+        let mut clock = GameClock::default();
+        clock.advance(LONG_TICK, false);
+        world.insert_resource(clock);
+        world.init_resource::<NextState<PacmanState>>();
+        world.init_resource::<Events<GhostEaten>>();
+        world.init_resource::<Events<PacmanDied>>();
+
+        let pos = UnitPosition { x: 2000, y: CORRIDOR_Y };
+        world.spawn((
+            Pacman::new(),
+            pos,
+            UnitLastPosition(pos),
+            UnitPath::default(),
+            SpriteAnimation::new("pacman_chomp", "pacman.left"),
+        ));
+        world
+    }
+
+    fn spawn_ghost(world: &mut World, ghost_id: GhostId, x: i32, is_frightened: bool) -> Entity {
+        let pos = UnitPosition { x, y: CORRIDOR_Y };
+        let mut ghost = Ghost::new(ghost_id, 0., UnitDirection::Right);
+        ghost.is_moved_out = true;
+        ghost.is_frightened = is_frightened;
+        world.spawn((ghost, pos, UnitLastPosition(pos), UnitPath::default())).id()
+    }
+
+    // Run one simulation tick, the ghosts walk the given pixel steps while
+    // pacman moves by himself
+    fn run_tick(world: &mut World, ghost_steps: &[(Entity, UnitDirection, u32)]) {
+        let mut schedule = Schedule::new();
+        schedule.add_system(store_unit_last_positions);
+        schedule.run(world);
+
+        for (entity, direction, steps) in ghost_steps {
+            let mut ghost = world.entity_mut(*entity);
+            let mut pos = *ghost.get::<UnitPosition>().unwrap();
+            let mut path = ghost.get_mut::<UnitPath>().unwrap();
+            for _ in 0..*steps {
+                assert!(unit_can_move_in_direction(&pos, *direction));
+                path.step(&mut pos, *direction);
+            }
+            ghost.insert(pos);
+        }
+
+        let mut schedule = Schedule::new();
+        schedule.add_systems((pacman_movement, pacman_touches_ghosts).chain());
+        schedule.run(world);
+    }
+
+    fn pacman_x(world: &mut World) -> i32 {
+        world.query_filtered::<&UnitPosition, With<Pacman>>().single(world).x
+    }
+
+    fn eaten_ghosts(world: &World) -> Vec<GhostId> {
+        world.resource::<Events<GhostEaten>>().iter_current_update_events().map(|event| event.ghost).collect()
+    }
+
+    fn is_pacman_dead(world: &World) -> bool {
+        world.resource::<NextState<PacmanState>>().0 == Some(PacmanState::Dead)
+            && world.resource::<Events<PacmanDied>>().len() == 1
+    }
+
+    #[test]
+    fn ghost_passing_head_on_kills_pacman() {
+        // Neither the start nor the end positions of the tick overlap
+        let mut world = setup_world();
+        let ghost = spawn_ghost(&mut world, GhostId::Blinky, 1880, false);
+        run_tick(&mut world, &[(ghost, UnitDirection::Right, 60)]);
+
+        assert_eq!(pacman_x(&mut world), 1910);
+        assert!(is_pacman_dead(&world));
+        assert!(eaten_ghosts(&world).is_empty());
+    }
+
+    #[test]
+    fn eyes_of_eaten_ghost_pass_through_pacman() {
+        let mut world = setup_world();
+        let ghost = spawn_ghost(&mut world, GhostId::Blinky, 1880, false);
+        world.get_mut::<Ghost>(ghost).unwrap().is_eaten = true;
+        run_tick(&mut world, &[(ghost, UnitDirection::Right, 60)]);
+
+        assert!(!is_pacman_dead(&world));
+        assert!(eaten_ghosts(&world).is_empty());
+    }
+
+    #[test]
+    fn frightened_ghost_passing_head_on_is_eaten() {
+        let mut world = setup_world();
+        let ghost = spawn_ghost(&mut world, GhostId::Pinky, 1880, true);
+        run_tick(&mut world, &[(ghost, UnitDirection::Right, 60)]);
+
+        assert!(!is_pacman_dead(&world));
+        assert_eq!(eaten_ghosts(&world), vec![GhostId::Pinky]);
+        assert_eq!(world.get::<UnitPosition>(ghost), Some(&UnitPosition { x: 1940, y: CORRIDOR_Y }));
+        assert!(world.get::<Ghost>(ghost).unwrap().is_eaten);
+    }
+
+    #[test]
+    fn ghost_turning_into_the_corridor_kills_pacman() {
+        // Blinky comes up the gap between the top walls and turns after
+        // pacman, who has passed the gap at the end of the tick
+        let mut world = setup_world();
+        world.query_filtered::<&mut UnitPosition, With<Pacman>>().single_mut(&mut world).x = 1780;
+        let ghost = spawn_ghost(&mut world, GhostId::Blinky, 1683, false);
+        world.get_mut::<UnitPosition>(ghost).unwrap().y = 1023;
+        run_tick(&mut world, &[(ghost, UnitDirection::Up, 60), (ghost, UnitDirection::Right, 60)]);
+
+        assert_eq!(pacman_x(&mut world), 1690);
+        assert_eq!(world.get::<UnitPosition>(ghost), Some(&UnitPosition { x: 1743, y: CORRIDOR_Y }));
+        assert!(is_pacman_dead(&world));
+    }
+
+    #[test]
+    fn contacts_are_resolved_in_the_order_pacman_meets_the_ghosts() {
+        // The frightened ghost is met first and eaten, then the next one
+        // kills pacman
+        let mut world = setup_world();
+        spawn_ghost(&mut world, GhostId::Clyde, 1960, true);
+        spawn_ghost(&mut world, GhostId::Blinky, 1930, false);
+        run_tick(&mut world, &[]);
+        assert_eq!(eaten_ghosts(&world), vec![GhostId::Clyde]);
+        assert!(is_pacman_dead(&world));
+
+        // Pacman is killed first and can not eat the frightened ghost
+        let mut world = setup_world();
+        spawn_ghost(&mut world, GhostId::Clyde, 1960, false);
+        spawn_ghost(&mut world, GhostId::Blinky, 1930, true);
+        run_tick(&mut world, &[]);
+        assert!(eaten_ghosts(&world).is_empty());
+        assert!(is_pacman_dead(&world));
+    }
+}
@@ -0,0 +1,105 @@
+//
+// Daniel Bauer (bauerda@pm.me)
+//
+
+use bevy::prelude::*;
+
+use super::events::*;
+use super::game::*;
+use super::packs::*;
+use super::states::*;
+use super::unit::*;
+
+const POPUP_FONT: &str = "font.popup";
+const POPUP_FONT_SIZE: f32 = 64.;
+const POPUP_Z: f32 = 10.;
+
+const POPUP_COLOR: Color = Color::CYAN;
+
+pub struct PopupsPlugin;
+
+impl Plugin for PopupsPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems((
+                spawn_popups,
+                despawn_expired_popups,
+            ))
+
+            // New Round State
+            .add_systems((
+                despawn_popups
+                    .in_schedule(OnEnter(GameState::NewRound)),
+            ))
+        ;
+    }
+}
+
+#[derive(Component)]
+pub struct ScorePopup {
+    pub despawn_time: f32,
+}
+
+fn spawn_popup(
+    commands: &mut Commands,
+    font: &Handle<Font>,
+    clock: &GameClock,
+    points: u32,
+    position: UnitPosition,
+) {
+    commands.spawn((
+        ScorePopup {
+            despawn_time: clock.elapsed_seconds(),
+        },
+        position,
+        UnitScale::square(1.),
+        Text2dBundle {
+            text: Text::from_section(
+                points.to_string(),
+                TextStyle {
+                    font: font.clone(),
+                    font_size: POPUP_FONT_SIZE,
+                    color: POPUP_COLOR,
+                },
+            ).with_alignment(TextAlignment::Center),
+            transform: Transform::from_xyz(0., 0., POPUP_Z),
+            ..default()
+        },
+    ));
+}
+
+pub fn spawn_popups(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    pack_assets: Res<PackAssets>,
+    clock: Res<GameClock>,
+    mut ghost_events: EventReader<GhostEaten>,
+) {
+    let font = pack_assets.load(&asset_server, POPUP_FONT);
+    for event in ghost_events.iter() {
+        spawn_popup(&mut commands, &font, &clock, event.points, event.position);
+    }
+}
+
+// The popups expire by the game clock, so a ghost popup stays until the
+// freeze is over and no popup expires while the game is paused
+pub fn despawn_expired_popups(
+    mut commands: Commands,
+    query_popups: Query<(Entity, &ScorePopup)>,
+    clock: Res<GameClock>,
+) {
+    for (entity, popup) in query_popups.iter() {
+        if clock.elapsed_seconds() > popup.despawn_time {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+pub fn despawn_popups(
+    mut commands: Commands,
+    query_popups: Query<Entity, With<ScorePopup>>,
+) {
+    for entity in query_popups.iter() {
+        commands.entity(entity).despawn();
+    }
+}
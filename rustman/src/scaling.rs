@@ -2,67 +2,174 @@
 // Daniel Bauer (bauerda@pm.me)
 //
 
+use std::f32::consts::FRAC_PI_2;
+
 use bevy::prelude::*;
+use bevy::render::camera::ScalingMode;
 
 use super::unit::*;
 use super::maze::*;
 use super::ui::UI_HEIGHT;
 
+// Height of the touch controls below the maze in portrait windows
+pub const TOUCH_CONTROLS_HEIGHT: f32 = 160.;
+
 pub struct ScalingPlugin;
 
 impl Plugin for ScalingPlugin {
     fn build(&self, app: &mut App) {
         app
+            .init_resource::<MazeScaling>()
+            .add_startup_system(setup_camera)
             .add_systems((
+                update_camera_projection,
                 position_translation,
+                interpolate_translation,
                 size_scaling,
             ))
         ;
     }
 }
 
+// The world is laid out in maze pixels, the camera scales it to fit into
+// the window below and above the HUD bars. In portrait windows the maze is
+// rotated to fill the window and the touch controls are placed below it.
+#[derive(Resource)]
+pub struct MazeScaling {
+    pub factor: f32,
+    pub is_portrait: bool,
+}
+
+impl Default for MazeScaling {
+    fn default() -> Self {
+        Self { factor: 1., is_portrait: false }
+    }
+}
+
+impl MazeScaling {
+    pub fn controls_height(&self) -> f32 {
+        if self.is_portrait { TOUCH_CONTROLS_HEIGHT } else { 0. }
+    }
+
+    // Width of the maze on the screen
+    pub fn screen_width(&self) -> f32 {
+        let width = if self.is_portrait { MAZE_HEIGHT } else { MAZE_WIDTH };
+        width as f32 * self.factor
+    }
+
+    // The direction in the maze which points to the given direction on the
+    // screen, the rotated maze has its left side at the top of the screen
+    pub fn maze_direction(&self, screen_direction: UnitDirection) -> UnitDirection {
+        if !self.is_portrait {
+            return screen_direction;
+        }
+        match screen_direction {
+            UnitDirection::Up => UnitDirection::Left,
+            UnitDirection::Right => UnitDirection::Up,
+            UnitDirection::Down => UnitDirection::Right,
+            UnitDirection::Left => UnitDirection::Down,
+            UnitDirection::None => UnitDirection::None,
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct MazeCamera;
+
+fn setup_camera(mut commands: Commands) {
+    commands.spawn((
+        Camera2dBundle::default(),
+        MazeCamera,
+    ));
+}
+
+// Fit the maze into the window when its size or orientation changed, the
+// rest of the window stays black
+fn update_camera_projection(
+    query_window: Query<&Window, Changed<Window>>,
+    mut query_camera: Query<(&mut OrthographicProjection, &mut Transform), With<MazeCamera>>,
+    mut scaling: ResMut<MazeScaling>,
+) {
+    let Ok(window) = query_window.get_single() else { return };
+    let is_portrait = window.height() > window.width();
+    let controls_height = if is_portrait { TOUCH_CONTROLS_HEIGHT } else { 0. };
+    let factor = calc_scaling_factor(window.width(), window.height() - controls_height, is_portrait);
+    if factor <= 0. || (factor == scaling.factor && is_portrait == scaling.is_portrait) { return; }
+
+    scaling.factor = factor;
+    scaling.is_portrait = is_portrait;
+    for (mut projection, mut transform) in query_camera.iter_mut() {
+        projection.scaling_mode = ScalingMode::WindowSize(factor);
+
+        // Rotate the maze in portrait windows and move it up above the
+        // touch controls
+        transform.rotation = Quat::from_rotation_z(if is_portrait { FRAC_PI_2 } else { 0. });
+        let offset = transform.rotation * Vec3::new(0., -controls_height / 2. / factor, 0.);
+        transform.translation.x = offset.x;
+        transform.translation.y = offset.y;
+    }
+}
+
 fn size_scaling(
-    mut query_window: Query<&mut Window>,
-    mut query_scale: Query<(&UnitScale, &mut Transform)>
+    mut query_scale: Query<(&UnitScale, &mut Transform), Changed<UnitScale>>,
 ) {
-    let window = query_window.single_mut();
     for (sprite_size, mut transform) in query_scale.iter_mut() {
-        let scaling_factor = calc_scaling_factor(window.width(), window.height());
-        transform.scale = Vec3::new(
-            sprite_size.width * scaling_factor,
-            sprite_size.height * scaling_factor,
-            1.0,
-        );
+        transform.scale = Vec3::new(sprite_size.width, sprite_size.height, 1.0);
     }
 }
 
+#[allow(clippy::type_complexity)]
 fn position_translation(
-    mut query_window: Query<&mut Window>,
-    mut query_pos: Query<(&UnitPosition, &mut Transform)>,
+    mut query_pos: Query<(&UnitPosition, &mut Transform), (Changed<UnitPosition>, Without<UnitLastPosition>)>,
 ) {
-    fn convert(pos: f32, bound_window: f32, bound_game: f32) -> f32 {
-        let tile_size = bound_window / bound_game;
-        pos / bound_game * bound_window - (bound_window / 2.) + (tile_size / 2.)
+    // Keep the z coordinate, it orders units drawn on top of each other
+    for (pos, mut transform) in query_pos.iter_mut() {
+        let translation = unit_translation(pos.x as f32, pos.y as f32);
+        transform.translation.x = translation.x;
+        transform.translation.y = translation.y;
     }
+}
 
-    let window = query_window.single_mut();
-    let scaling_factor = calc_scaling_factor(window.width(), window.height());
-    let scaled_width = MAZE_WIDTH as f32 * scaling_factor; 
-    let scaled_height = MAZE_HEIGHT as f32 * scaling_factor;
-
-    for (pos, mut transform) in query_pos.iter_mut() {
-        transform.translation = Vec3::new(
-            convert(pos.x as f32, scaled_width, MAZE_WIDTH as f32),
-            convert(pos.y as f32, scaled_height, MAZE_HEIGHT as f32),
-            0.0,
+// Moving units are drawn between their positions of the last and the
+// current simulation tick by the time passed since the current tick, so
+// they move smoothly at any frame rate. Units which jumped, through the
+// tunnel or back into the ghost house, are drawn at their new position.
+fn interpolate_translation(
+    fixed_time: Res<FixedTime>,
+    mut query_pos: Query<(&UnitPosition, &UnitLastPosition, &mut Transform)>,
+) {
+    let alpha = (fixed_time.accumulated().as_secs_f32() / fixed_time.period.as_secs_f32()).clamp(0., 1.);
+    for (pos, last_pos, mut transform) in query_pos.iter_mut() {
+        let distance = (pos.x - last_pos.0.x).abs() + (pos.y - last_pos.0.y).abs();
+        let from = if distance > UNIT_SIZE as i32 { pos } else { &last_pos.0 };
+        let translation = unit_translation(
+            from.x as f32 + (pos.x - from.x) as f32 * alpha,
+            from.y as f32 + (pos.y - from.y) as f32 * alpha,
         );
+        // Units standing still keep their transform untouched, so it isn't
+        // marked as changed every frame
+        if pos == &last_pos.0 && transform.translation.truncate() == translation {
+            continue;
+        }
+        transform.translation.x = translation.x;
+        transform.translation.y = translation.y;
     }
 }
 
-fn calc_scaling_factor(window_width: f32, window_height: f32) -> f32 {
+// The translation of a position in the maze, which is centered in the world
+pub fn unit_translation(x: f32, y: f32) -> Vec2 {
+    Vec2::new(x - MAZE_WIDTH as f32 / 2., y - MAZE_HEIGHT as f32 / 2.)
+}
+
+fn calc_scaling_factor(window_width: f32, window_height: f32, is_rotated: bool) -> f32 {
+    let (maze_width, maze_height) = if is_rotated {
+        (MAZE_HEIGHT as f32, MAZE_WIDTH as f32)
+    } else {
+        (MAZE_WIDTH as f32, MAZE_HEIGHT as f32)
+    };
     let height = window_height - UI_HEIGHT as f32;
-    let scaling_factor_x = window_width / MAZE_WIDTH as f32;
-    let scaling_factor_y = height / MAZE_HEIGHT as f32;
-    let scaled_height = MAZE_HEIGHT as f32 * scaling_factor_x;
+    let scaling_factor_x = window_width / maze_width;
+    let scaling_factor_y = height / maze_height;
+    let scaled_height = maze_height * scaling_factor_x;
     if scaled_height > height { scaling_factor_y } else { scaling_factor_x }
-}
\ No newline at end of file
+}
@@ -0,0 +1,474 @@
+//
+// Daniel Bauer (bauerda@pm.me)
+//
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::ghosts::*;
+use super::music::*;
+use super::packs::*;
+use super::sound::*;
+use super::states::*;
+use super::storage;
+
+const SETTINGS_KEY: &str = "settings";
+const VOLUME_STEP: f32 = 0.1;
+
+pub const GAME_SPEED_MIN: u32 = 50;
+pub const GAME_SPEED_MAX: u32 = 150;
+const GAME_SPEED_STEP: u32 = 10;
+
+pub struct SettingsPlugin;
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource(storage::load::<Settings>(SETTINGS_KEY).unwrap_or_default())
+            .init_resource::<SettingsMenu>()
+            .add_systems((
+                toggle_settings_menu,
+                mute_input,
+                settings_menu_input
+                    .after(toggle_settings_menu),
+                pause_while_settings_menu_open
+                    .in_set(OnUpdate(GameState::Running)),
+                resume_after_settings_menu
+                    .in_set(OnUpdate(GameState::Paused)),
+                update_settings_menu
+                    .after(settings_menu_input),
+                save_settings,
+            ))
+        ;
+    }
+}
+
+#[derive(Resource, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub gameplay: GameplaySettings,
+    pub audio: AudioSettings,
+    pub accessibility: AccessibilitySettings,
+    pub assets: AssetSettings,
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GameplaySettings {
+    // Speed of the gameplay in percent of the normal speed
+    pub speed_percent: u32,
+}
+
+impl Default for GameplaySettings {
+    fn default() -> Self {
+        Self {
+            speed_percent: 100,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AudioSettings {
+    pub master_volume: f32,
+    pub music_volume: f32,
+    pub siren_volume: f32,
+    pub sfx_volume: f32,
+    pub ui_volume: f32,
+    pub is_muted: bool,
+    pub is_panning: bool,
+    pub sound_pack: SoundPack,
+    pub music_mode: MusicMode,
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        Self {
+            master_volume: 1.,
+            music_volume: 1.,
+            siren_volume: 1.,
+            sfx_volume: 1.,
+            ui_volume: 1.,
+            is_muted: false,
+            is_panning: true,
+            sound_pack: SoundPack::Classic,
+            music_mode: MusicMode::Classic,
+        }
+    }
+}
+
+impl AudioSettings {
+    pub fn volume(&self, channel: SoundChannel) -> f32 {
+        if self.is_muted {
+            return 0.;
+        }
+        let channel_volume = match channel {
+            SoundChannel::Music => self.music_volume,
+            SoundChannel::Siren => self.siren_volume,
+            SoundChannel::Sfx => self.sfx_volume,
+            SoundChannel::Ui => self.ui_volume,
+        };
+        self.master_volume * channel_volume
+    }
+}
+
+#[derive(Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AccessibilitySettings {
+    pub is_reduced_flash: bool,
+    pub ghost_palette: GhostPalette,
+    pub is_ghost_markers: bool,
+    pub is_high_contrast: bool,
+    pub is_large_text: bool,
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AssetSettings {
+    // Name of the asset pack in the packs folder, it is loaded at the start
+    pub pack: String,
+}
+
+impl Default for AssetSettings {
+    fn default() -> Self {
+        Self {
+            pack: DEFAULT_ASSET_PACK.to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum SettingsGroup {
+    Gameplay,
+    Audio,
+    Accessibility,
+    Assets,
+}
+
+impl SettingsGroup {
+    fn label(self) -> &'static str {
+        match self {
+            SettingsGroup::Gameplay => "Gameplay",
+            SettingsGroup::Audio => "Audio",
+            SettingsGroup::Accessibility => "Accessibility",
+            SettingsGroup::Assets => "Assets",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum SettingsItem {
+    GameSpeed,
+    MasterVolume,
+    MusicVolume,
+    SirenVolume,
+    SfxVolume,
+    UiVolume,
+    SoundPack,
+    MusicMode,
+    Panning,
+    Mute,
+    ReducedFlash,
+    GhostPalette,
+    GhostMarkers,
+    HighContrast,
+    LargeText,
+    AssetPack,
+}
+
+const SETTINGS_ITEMS: [SettingsItem; 16] = [
+    SettingsItem::GameSpeed,
+    SettingsItem::MasterVolume,
+    SettingsItem::MusicVolume,
+    SettingsItem::SirenVolume,
+    SettingsItem::SfxVolume,
+    SettingsItem::UiVolume,
+    SettingsItem::SoundPack,
+    SettingsItem::MusicMode,
+    SettingsItem::Panning,
+    SettingsItem::Mute,
+    SettingsItem::ReducedFlash,
+    SettingsItem::GhostPalette,
+    SettingsItem::GhostMarkers,
+    SettingsItem::HighContrast,
+    SettingsItem::LargeText,
+    SettingsItem::AssetPack,
+];
+
+impl SettingsItem {
+    fn group(self) -> SettingsGroup {
+        match self {
+            SettingsItem::GameSpeed => SettingsGroup::Gameplay,
+            SettingsItem::ReducedFlash
+            | SettingsItem::GhostPalette
+            | SettingsItem::GhostMarkers
+            | SettingsItem::HighContrast
+            | SettingsItem::LargeText => SettingsGroup::Accessibility,
+            SettingsItem::AssetPack => SettingsGroup::Assets,
+            _ => SettingsGroup::Audio,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            SettingsItem::GameSpeed => "Game Speed",
+            SettingsItem::MasterVolume => "Master Volume",
+            SettingsItem::MusicVolume => "Music Volume",
+            SettingsItem::SirenVolume => "Siren Volume",
+            SettingsItem::SfxVolume => "Effects Volume",
+            SettingsItem::UiVolume => "Interface Volume",
+            SettingsItem::SoundPack => "Sound Pack",
+            SettingsItem::MusicMode => "Music",
+            SettingsItem::Panning => "Stereo Panning",
+            SettingsItem::Mute => "Mute (M)",
+            SettingsItem::ReducedFlash => "Reduced Flashing",
+            SettingsItem::GhostPalette => "Ghost Colors",
+            SettingsItem::GhostMarkers => "Ghost Markers",
+            SettingsItem::HighContrast => "High Contrast",
+            SettingsItem::LargeText => "Large Text",
+            SettingsItem::AssetPack => "Asset Pack",
+        }
+    }
+
+    // A pack other than the loaded one is used after a restart
+    fn value(self, settings: &Settings, pack_assets: &PackAssets) -> String {
+        let percent = |volume: f32| format!("{:.0}%", volume * 100.);
+        let on_off = |value: bool| if value { "On".to_string() } else { "Off".to_string() };
+        match self {
+            SettingsItem::GameSpeed => format!("{}%", settings.gameplay.speed_percent),
+            SettingsItem::MasterVolume => percent(settings.audio.master_volume),
+            SettingsItem::MusicVolume => percent(settings.audio.music_volume),
+            SettingsItem::SirenVolume => percent(settings.audio.siren_volume),
+            SettingsItem::SfxVolume => percent(settings.audio.sfx_volume),
+            SettingsItem::UiVolume => percent(settings.audio.ui_volume),
+            SettingsItem::SoundPack => settings.audio.sound_pack.label().to_string(),
+            SettingsItem::MusicMode => settings.audio.music_mode.label().to_string(),
+            SettingsItem::Panning => on_off(settings.audio.is_panning),
+            SettingsItem::Mute => on_off(settings.audio.is_muted),
+            SettingsItem::ReducedFlash => on_off(settings.accessibility.is_reduced_flash),
+            SettingsItem::GhostPalette => settings.accessibility.ghost_palette.label().to_string(),
+            SettingsItem::GhostMarkers => on_off(settings.accessibility.is_ghost_markers),
+            SettingsItem::HighContrast => on_off(settings.accessibility.is_high_contrast),
+            SettingsItem::LargeText => on_off(settings.accessibility.is_large_text),
+            SettingsItem::AssetPack if settings.assets.pack != pack_assets.pack() => {
+                format!("{} (restart)", settings.assets.pack)
+            }
+            SettingsItem::AssetPack => settings.assets.pack.clone(),
+        }
+    }
+
+    fn change(self, settings: &mut Settings, step: i32) {
+        let change_volume = |volume: &mut f32| {
+            *volume = (*volume + step as f32 * VOLUME_STEP).clamp(0., 1.);
+        };
+        match self {
+            SettingsItem::GameSpeed => {
+                let speed = settings.gameplay.speed_percent as i32 + step * GAME_SPEED_STEP as i32;
+                settings.gameplay.speed_percent = (speed.max(0) as u32).clamp(GAME_SPEED_MIN, GAME_SPEED_MAX);
+            }
+            SettingsItem::MasterVolume => change_volume(&mut settings.audio.master_volume),
+            SettingsItem::MusicVolume => change_volume(&mut settings.audio.music_volume),
+            SettingsItem::SirenVolume => change_volume(&mut settings.audio.siren_volume),
+            SettingsItem::SfxVolume => change_volume(&mut settings.audio.sfx_volume),
+            SettingsItem::UiVolume => change_volume(&mut settings.audio.ui_volume),
+            SettingsItem::SoundPack => settings.audio.sound_pack = settings.audio.sound_pack.next(),
+            SettingsItem::MusicMode => settings.audio.music_mode = settings.audio.music_mode.next(),
+            SettingsItem::Panning => settings.audio.is_panning = !settings.audio.is_panning,
+            SettingsItem::Mute => settings.audio.is_muted = !settings.audio.is_muted,
+            SettingsItem::ReducedFlash => {
+                settings.accessibility.is_reduced_flash = !settings.accessibility.is_reduced_flash;
+            }
+            SettingsItem::GhostPalette => {
+                settings.accessibility.ghost_palette = settings.accessibility.ghost_palette.next();
+            }
+            SettingsItem::GhostMarkers => {
+                settings.accessibility.is_ghost_markers = !settings.accessibility.is_ghost_markers;
+            }
+            SettingsItem::HighContrast => {
+                settings.accessibility.is_high_contrast = !settings.accessibility.is_high_contrast;
+            }
+            SettingsItem::LargeText => {
+                settings.accessibility.is_large_text = !settings.accessibility.is_large_text;
+            }
+            SettingsItem::AssetPack => {
+                let count = ASSET_PACKS.len() as i32;
+                let index = ASSET_PACKS.iter().position(|pack| *pack == settings.assets.pack).unwrap_or(0) as i32;
+                settings.assets.pack = ASSET_PACKS[(index + step).rem_euclid(count) as usize].to_string();
+            }
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct SettingsMenu {
+    pub is_open: bool,
+    selected: usize,
+    // Whether opening the menu paused the game, it is resumed on closing
+    is_pausing: bool,
+}
+
+#[derive(Component)]
+struct SettingsMenuRoot;
+
+#[derive(Component)]
+struct SettingsMenuText;
+
+fn toggle_settings_menu(
+    keys: Res<Input<KeyCode>>,
+    mut menu: ResMut<SettingsMenu>,
+    mut sounds: Sounds,
+) {
+    if keys.just_pressed(KeyCode::Escape) {
+        menu.is_open = !menu.is_open;
+        sounds.play(SoundChannel::Ui, SoundId::MenuSelect);
+    }
+}
+
+fn mute_input(
+    keys: Res<Input<KeyCode>>,
+    mut settings: ResMut<Settings>,
+) {
+    if keys.just_pressed(KeyCode::M) {
+        settings.audio.is_muted = !settings.audio.is_muted;
+    }
+}
+
+fn settings_menu_input(
+    keys: Res<Input<KeyCode>>,
+    mut menu: ResMut<SettingsMenu>,
+    mut settings: ResMut<Settings>,
+    mut sounds: Sounds,
+) {
+    if !menu.is_open { return; }
+
+    let item = SETTINGS_ITEMS[menu.selected];
+    if keys.just_pressed(KeyCode::Up) {
+        menu.selected = (menu.selected + SETTINGS_ITEMS.len() - 1) % SETTINGS_ITEMS.len();
+    } else if keys.just_pressed(KeyCode::Down) {
+        menu.selected = (menu.selected + 1) % SETTINGS_ITEMS.len();
+    } else if keys.just_pressed(KeyCode::Left) {
+        item.change(&mut settings, -1);
+    } else if keys.just_pressed(KeyCode::Right) || keys.just_pressed(KeyCode::Return) {
+        item.change(&mut settings, 1);
+    } else {
+        return;
+    }
+    sounds.play(SoundChannel::Ui, SoundId::MenuMove);
+}
+
+fn pause_while_settings_menu_open(
+    mut menu: ResMut<SettingsMenu>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if menu.is_open {
+        menu.is_pausing = true;
+        next_state.set(GameState::Paused);
+    }
+}
+
+// A game paused by the player before opening the menu stays paused
+fn resume_after_settings_menu(
+    mut menu: ResMut<SettingsMenu>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if !menu.is_open && menu.is_pausing {
+        menu.is_pausing = false;
+        next_state.set(GameState::Running);
+    }
+}
+
+fn update_settings_menu(
+    mut commands: Commands,
+    menu: Res<SettingsMenu>,
+    settings: Res<Settings>,
+    asset_server: Res<AssetServer>,
+    pack_assets: Res<PackAssets>,
+    query_root: Query<Entity, With<SettingsMenuRoot>>,
+    mut query_text: Query<&mut Text, With<SettingsMenuText>>,
+) {
+    if !menu.is_changed() && !settings.is_changed() { return; }
+
+    // Close the menu
+    if !menu.is_open {
+        for entity in query_root.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+        return;
+    }
+
+    let text_style = TextStyle {
+        font: pack_assets.load(&asset_server, "font.menu"),
+        font_size: 20.0,
+        color: Color::GRAY,
+    };
+    let mut sections: Vec<TextSection> = Vec::new();
+    for (i, item) in SETTINGS_ITEMS.iter().enumerate() {
+        // Start a new group with its heading
+        if i == 0 || SETTINGS_ITEMS[i - 1].group() != item.group() {
+            sections.push(TextSection::new(
+                format!("{}{}\n", if i == 0 { "" } else { "\n" }, item.group().label()),
+                TextStyle {
+                    color: Color::WHITE,
+                    ..text_style.clone()
+                },
+            ));
+        }
+
+        let is_selected = i == menu.selected;
+        sections.push(TextSection::new(
+            format!("{} {:<20}{:>10}\n", if is_selected { ">" } else { " " }, item.label(), item.value(&settings, &pack_assets)),
+            TextStyle {
+                color: if is_selected { Color::YELLOW } else { Color::GRAY },
+                ..text_style.clone()
+            },
+        ));
+    }
+
+    // Update the open menu
+    if let Some(mut text) = query_text.iter_mut().next() {
+        text.sections = sections;
+        return;
+    }
+
+    // Open the menu
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    flex_direction: FlexDirection::Column,
+                    ..default()
+                },
+                background_color: Color::rgba(0., 0., 0., 0.85).into(),
+                z_index: ZIndex::Global(10),
+                ..default()
+            },
+            SettingsMenuRoot,
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                "Settings",
+                TextStyle {
+                    font: pack_assets.load(&asset_server, "font.menu_title"),
+                    font_size: 30.0,
+                    color: Color::YELLOW,
+                },
+            ).with_style(Style {
+                margin: UiRect::bottom(Val::Px(20.)),
+                ..default()
+            }));
+            parent.spawn((
+                TextBundle::from_sections(sections),
+                SettingsMenuText,
+            ));
+        });
+}
+
+fn save_settings(settings: Res<Settings>) {
+    if settings.is_changed() && !settings.is_added() {
+        storage::save(SETTINGS_KEY, &*settings);
+    }
+}
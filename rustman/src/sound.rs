@@ -3,41 +3,533 @@
 //
 
 use bevy::prelude::*;
+use bevy::audio::{AddAudioSource, AudioPlugin};
+use bevy::ecs::system::SystemParam;
+use bevy::utils::HashMap;
+use serde::{Deserialize, Serialize};
 
+use super::events::*;
 use super::game::*;
+use super::ghosts::*;
+use super::maze::*;
+use super::music::*;
+use super::packs::*;
+use super::settings::*;
 use super::states::*;
+use super::synth::*;
+use super::unit::*;
 
-pub const SOUND_DURATION_START: f32 = 5.;
-pub const SOUND_DURATION_AMBIENT_SIREN: f32 = 0.45;
-pub const SOUND_DURATION_AMBIENT_FRIGHT: f32 = 0.55;
+const SYNTH_SOUND_PACK: &str = "sounds.synth";
+
+const AMBIENT_CROSSFADE_DURATION: f32 = 0.3;
+const AMBIENT_SIREN_STAGES: u32 = 4;
+
+// Stages the siren of the later rounds starts above the first stage, it
+// still rises to the top stage while the dots are eaten
+const AMBIENT_SIREN_ROUND_OFFSET_MAX: u32 = AMBIENT_SIREN_STAGES - 2;
+
+// Number of sounds per channel which are kept for stopping them or
+// changing their volume, older sounds just play until they are finished
+const CHANNEL_SINK_LIMIT: usize = 16;
+
+// Distance between the ears of the listener, part of it used for panning
+// and the volume which keeps panned sounds as loud as centered ones
+const SOUND_PAN_EAR_GAP: f32 = 1.;
+const SOUND_PAN_WIDTH: f32 = 0.8;
+const SOUND_PAN_VOLUME: f32 = 1. / 0.75;
 
 pub struct SoundPlugin;
 
 impl Plugin for SoundPlugin {
     fn build(&self, app: &mut App) {
+        // The synthesized sounds need their own audio source, which is only
+        // added along with the audio plugin
+        if app.is_plugin_added::<AudioPlugin>() {
+            app
+                .add_audio_source::<SynthSound>()
+                .init_asset_loader::<SynthPackLoader>();
+        } else {
+            info!("Audio is disabled by the --no-audio flag, the game runs without sound");
+        }
+
         app
+            .init_resource::<SoundChannels>()
+            .init_resource::<AmbientSound>()
             .add_systems((
+                play_start_sound
+                    .in_schedule(OnEnter(GameState::Start)),
+                play_event_sounds,
+                play_pause_sound
+                    .in_schedule(OnEnter(GameState::Paused)),
                 play_ambient_sound
                     .in_set(OnUpdate(GameState::Running)),
+                apply_sound_volumes,
+                stop_ambient_sound
+                    .in_schedule(OnExit(GameState::Running)),
+                stop_ambient_sound
+                    .in_schedule(OnEnter(PacmanState::Dead)),
             ))
         ;
     }
 }
 
+// The sounds of the game, which are played from the asset files of the
+// classic sound pack or synthesized by the synth sound pack. The classic
+// sounds are looked up in the asset pack by "sound." and the synth name,
+// sounds without a file only exist in the synth sound pack.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SoundId {
+    Start,
+    Eat,
+    Eat2,
+    EatGhost,
+    ExtraLife,
+    Death,
+    Siren(u32),
+    Fright,
+    Eyes,
+    Pause,
+    MenuMove,
+    MenuSelect,
+    Music(MusicStem),
+}
+
+impl SoundId {
+    fn classic_id(self) -> String {
+        format!("sound.{}", self.synth_name())
+    }
+
+    fn synth_name(self) -> String {
+        match self {
+            SoundId::Start => "start".to_string(),
+            SoundId::Eat => "eat".to_string(),
+            SoundId::Eat2 => "eat2".to_string(),
+            SoundId::EatGhost => "eat_ghost".to_string(),
+            SoundId::ExtraLife => "extra_life".to_string(),
+            SoundId::Death => "death".to_string(),
+            SoundId::Siren(stage) => format!("siren{}", stage),
+            SoundId::Fright => "fright".to_string(),
+            SoundId::Eyes => "eyes".to_string(),
+            SoundId::Pause => "pause".to_string(),
+            SoundId::MenuMove => "menu_move".to_string(),
+            SoundId::MenuSelect => "menu_select".to_string(),
+            SoundId::Music(stem) => format!("music_{}", stem.name()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SoundPack {
+    #[default]
+    Classic,
+    Synth,
+}
+
+impl SoundPack {
+    pub fn label(self) -> &'static str {
+        match self {
+            SoundPack::Classic => "Classic",
+            SoundPack::Synth => "Synth",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            SoundPack::Classic => SoundPack::Synth,
+            SoundPack::Synth => SoundPack::Classic,
+        }
+    }
+}
+
+enum SoundSource {
+    Classic(Handle<AudioSource>),
+    Synth(Handle<SynthSound>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SoundChannel {
+    Music,
+    Siren,
+    Sfx,
+    Ui,
+}
+
+// The sounds played on each channel together with their own volume,
+// which is multiplied with the volume of the channel
+#[derive(Resource, Default)]
+pub struct SoundChannels {
+    sinks: HashMap<SoundChannel, Vec<(SoundSink, f32)>>,
+}
+
+// The ambient sound loops, the current track is faded in while all
+// other tracks are faded out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AmbientTrack {
+    Siren(u32),
+    Fright,
+    Eyes,
+}
+
+impl AmbientTrack {
+    fn sound(self) -> SoundId {
+        match self {
+            AmbientTrack::Siren(stage) => SoundId::Siren(stage),
+            AmbientTrack::Fright => SoundId::Fright,
+            AmbientTrack::Eyes => SoundId::Eyes,
+        }
+    }
+}
+
+struct AmbientLoop {
+    track: AmbientTrack,
+    sink: SoundSink,
+    volume: f32,
+}
+
+#[derive(Resource, Default)]
+pub struct AmbientSound {
+    loops: Vec<AmbientLoop>,
+}
+
+// A playing sound, panned sounds are played through a spatial sink whose
+// emitter is moved between the ears of the listener
+#[derive(Clone, PartialEq, Eq)]
+pub enum SoundSink {
+    Mono(Handle<AudioSink>),
+    Panned(Handle<SpatialAudioSink>),
+}
+
+// Plays sounds on a channel with the volume of the channel, all sounds
+// should be played through this instead of using the audio directly.
+// Without the audio plugin there are no audio resources, nothing is
+// played and a default handle is returned.
+#[derive(SystemParam)]
+pub struct Sounds<'w> {
+    asset_server: Res<'w, AssetServer>,
+    pack_assets: Res<'w, PackAssets>,
+    audio: Option<Res<'w, Audio>>,
+    synth_audio: Option<Res<'w, Audio<SynthSound>>>,
+    audio_sinks: Option<Res<'w, Assets<AudioSink>>>,
+    spatial_audio_sinks: Option<Res<'w, Assets<SpatialAudioSink>>>,
+    channels: ResMut<'w, SoundChannels>,
+    settings: Res<'w, Settings>,
+}
+
+impl Sounds<'_> {
+    pub fn play(&mut self, channel: SoundChannel, sound: SoundId) -> SoundSink {
+        self.play_with_settings(channel, sound, PlaybackSettings::ONCE)
+    }
+
+    pub fn play_with_settings(
+        &mut self,
+        channel: SoundChannel,
+        sound: SoundId,
+        settings: PlaybackSettings,
+    ) -> SoundSink {
+        let Some(audio_sinks) = &self.audio_sinks else {
+            return SoundSink::Mono(Handle::default());
+        };
+
+        let playback = settings.with_volume(settings.volume * self.settings.audio.volume(channel));
+        let sink = match (self.source(sound), &self.audio, &self.synth_audio) {
+            (Some(SoundSource::Classic(source)), Some(audio), _) => audio.play_with_settings(source, playback),
+            (Some(SoundSource::Synth(source)), _, Some(synth_audio)) => synth_audio.play_with_settings(source, playback),
+            _ => return SoundSink::Mono(Handle::default()),
+        };
+        self.add_sink(channel, SoundSink::Mono(audio_sinks.get_handle(sink)), settings.volume)
+    }
+
+    // Play a sound panned by the horizontal position of its source in the
+    // maze, the sound is played centered if panning is disabled
+    pub fn play_at(&mut self, channel: SoundChannel, sound: SoundId, pos: &UnitPosition) -> SoundSink {
+        self.play_at_with_settings(channel, sound, pos, PlaybackSettings::ONCE)
+    }
+
+    pub fn play_at_with_settings(
+        &mut self,
+        channel: SoundChannel,
+        sound: SoundId,
+        pos: &UnitPosition,
+        settings: PlaybackSettings,
+    ) -> SoundSink {
+        let Some(spatial_audio_sinks) = &self.spatial_audio_sinks else {
+            return SoundSink::Mono(Handle::default());
+        };
+
+        let volume = self.settings.audio.volume(channel) * SOUND_PAN_VOLUME;
+        let playback = settings.with_volume(settings.volume * volume);
+        let emitter = self.pan_emitter(pos);
+        let sink = match (self.source(sound), &self.audio, &self.synth_audio) {
+            (Some(SoundSource::Classic(source)), Some(audio), _) =>
+                audio.play_spatial_with_settings(source, playback, Transform::IDENTITY, SOUND_PAN_EAR_GAP, emitter),
+            (Some(SoundSource::Synth(source)), _, Some(synth_audio)) =>
+                synth_audio.play_spatial_with_settings(source, playback, Transform::IDENTITY, SOUND_PAN_EAR_GAP, emitter),
+            _ => return SoundSink::Mono(Handle::default()),
+        };
+        self.add_sink(channel, SoundSink::Panned(spatial_audio_sinks.get_handle(sink)), settings.volume)
+    }
+
+    // The music stems only exist as synthesized sounds, the sounds are
+    // already loaded with the asset pack
+    fn source(&self, sound: SoundId) -> Option<SoundSource> {
+        match self.settings.audio.sound_pack {
+            SoundPack::Classic if !matches!(sound, SoundId::Music(_)) => self.pack_assets
+                .path(&sound.classic_id())
+                .map(|path| SoundSource::Classic(self.asset_server.load(path))),
+            _ => self.pack_assets
+                .path(SYNTH_SOUND_PACK)
+                .map(|path| SoundSource::Synth(
+                    self.asset_server.load(format!("{}#{}", path, sound.synth_name())),
+                )),
+        }
+    }
+
+    // Move a panned sound to the horizontal position of its source
+    pub fn set_position(&mut self, sink: &SoundSink, pos: &UnitPosition) {
+        if let SoundSink::Panned(sink) = sink {
+            if let Some(spatial_audio_sink) = self.spatial_audio_sinks.as_ref().and_then(|sinks| sinks.get(sink)) {
+                spatial_audio_sink.set_emitter_position(self.pan_emitter(pos));
+            }
+        }
+    }
+
+    // Change the volume of a single sound relative to its channel
+    pub fn set_volume(&mut self, channel: SoundChannel, sink: &SoundSink, volume: f32) {
+        self.set_sink_volume(sink, volume * self.settings.audio.volume(channel));
+        if let Some(sinks) = self.channels.sinks.get_mut(&channel) {
+            for (_, sink_volume) in sinks.iter_mut().filter(|(channel_sink, _)| channel_sink == sink) {
+                *sink_volume = volume;
+            }
+        }
+    }
+
+    // Apply the volumes of the channels to all their sounds
+    pub fn apply_volumes(&self) {
+        for (channel, sinks) in self.channels.sinks.iter() {
+            let volume = self.settings.audio.volume(*channel);
+            for (sink, sink_volume) in sinks.iter() {
+                self.set_sink_volume(sink, sink_volume * volume);
+            }
+        }
+    }
+
+    pub fn stop_sink(&mut self, channel: SoundChannel, sink: &SoundSink) {
+        self.stop_audio_sink(sink);
+        if let Some(sinks) = self.channels.sinks.get_mut(&channel) {
+            sinks.retain(|(channel_sink, _)| channel_sink != sink);
+        }
+    }
+
+    pub fn stop(&mut self, channel: SoundChannel) {
+        for (sink, _) in self.channels.sinks.remove(&channel).unwrap_or_default() {
+            self.stop_audio_sink(&sink);
+        }
+    }
+
+    fn add_sink(&mut self, channel: SoundChannel, sink: SoundSink, volume: f32) -> SoundSink {
+        let sinks = self.channels.sinks.entry(channel).or_default();
+        if sinks.len() >= CHANNEL_SINK_LIMIT {
+            sinks.remove(0);
+        }
+        sinks.push((sink.clone(), volume));
+        sink
+    }
+
+    // The listener is placed at the origin with its ears on the x axis,
+    // rodio 0.17 raises the volume of the ear farther away from the
+    // emitter, so the emitter is placed on the opposite side
+    fn pan_emitter(&self, pos: &UnitPosition) -> Vec3 {
+        if !self.settings.audio.is_panning {
+            return Vec3::ZERO;
+        }
+        let pan = (pos.x as f32 / MAZE_WIDTH as f32 * 2. - 1.).clamp(-1., 1.);
+        Vec3::new(-pan * SOUND_PAN_WIDTH * SOUND_PAN_EAR_GAP / 2., 0., 0.)
+    }
+
+    fn set_sink_volume(&self, sink: &SoundSink, volume: f32) {
+        match sink {
+            SoundSink::Mono(sink) => {
+                if let Some(audio_sink) = self.audio_sinks.as_ref().and_then(|sinks| sinks.get(sink)) {
+                    audio_sink.set_volume(volume);
+                }
+            }
+            SoundSink::Panned(sink) => {
+                if let Some(audio_sink) = self.spatial_audio_sinks.as_ref().and_then(|sinks| sinks.get(sink)) {
+                    audio_sink.set_volume(volume * SOUND_PAN_VOLUME);
+                }
+            }
+        }
+    }
+
+    fn stop_audio_sink(&self, sink: &SoundSink) {
+        match sink {
+            SoundSink::Mono(sink) => {
+                if let Some(audio_sink) = self.audio_sinks.as_ref().and_then(|sinks| sinks.get(sink)) {
+                    audio_sink.stop();
+                }
+            }
+            SoundSink::Panned(sink) => {
+                if let Some(audio_sink) = self.spatial_audio_sinks.as_ref().and_then(|sinks| sinks.get(sink)) {
+                    audio_sink.stop();
+                }
+            }
+        }
+    }
+}
+
+pub fn play_start_sound(mut sounds: Sounds) {
+    sounds.play(SoundChannel::Music, SoundId::Start);
+}
+
+pub fn play_pause_sound(mut sounds: Sounds) {
+    sounds.play(SoundChannel::Ui, SoundId::Pause);
+}
+
+// Play the effects of the gameplay events, eating pellets alternates
+// between two sounds
+#[allow(clippy::too_many_arguments)]
+pub fn play_event_sounds(
+    mut dot_events: EventReader<DotEaten>,
+    mut energizer_events: EventReader<EnergizerEaten>,
+    mut ghost_events: EventReader<GhostEaten>,
+    mut died_events: EventReader<PacmanDied>,
+    mut extra_life_events: EventReader<ExtraLife>,
+    mut is_second_eat_sound: Local<bool>,
+    mut sounds: Sounds,
+) {
+    let pellets = dot_events.iter().count() + energizer_events.iter().count();
+    for _ in 0..pellets {
+        sounds.play(SoundChannel::Sfx,
+            if *is_second_eat_sound { SoundId::Eat2 } else { SoundId::Eat });
+        *is_second_eat_sound = !*is_second_eat_sound;
+    }
+    for event in ghost_events.iter() {
+        sounds.play_at(SoundChannel::Sfx, SoundId::EatGhost, &event.position);
+    }
+    for _ in died_events.iter() {
+        sounds.play(SoundChannel::Sfx, SoundId::Death);
+    }
+    for _ in extra_life_events.iter() {
+        sounds.play(SoundChannel::Sfx, SoundId::ExtraLife);
+    }
+}
+
+// Loop the ambient track matching the current situation, the siren rises
+// with the round and the dots eaten in the round. The eyes loop plays while
+// the eyes of eaten ghosts return to the ghost house door and is panned to
+// them.
 pub fn play_ambient_sound(
-    mut game: ResMut<Game>,
+    game: Res<Game>,
+    pellets: Res<Pellets>,
     pacman_state: Res<State<PacmanState>>,
-    asset_server: Res<AssetServer>,
-    audio: Res<Audio>,
+    query_ghosts: Query<(&Ghost, &UnitPosition)>,
+    mut ambient: ResMut<AmbientSound>,
+    mut sounds: Sounds,
     time: Res<Time>,
 ) {
-    let elapsed = time.elapsed_seconds() - game.elapsed_time_sound;
-    if elapsed >= SOUND_DURATION_AMBIENT_FRIGHT && pacman_state.0 == PacmanState::Energized {
-        audio.play(asset_server.load("sounds/ambient_fright.ogg"));
-        game.elapsed_time_sound = time.elapsed_seconds();
-    } else if elapsed >= SOUND_DURATION_AMBIENT_SIREN && pacman_state.0 == PacmanState::Normal {
-        let ambient_index = if game.round > 4 { 4 } else { game.round };
-        audio.play(asset_server.load(format!("sounds/ambient{}.ogg", ambient_index)));
-        game.elapsed_time_sound = time.elapsed_seconds();
+    let track = if pacman_state.0 == PacmanState::Dead {
+        None
+    } else if query_ghosts.iter().any(|(ghost, _)| ghost.is_returning_to_house()) {
+        Some(AmbientTrack::Eyes)
+    } else if pacman_state.0 == PacmanState::Energized {
+        Some(AmbientTrack::Fright)
+    } else {
+        Some(AmbientTrack::Siren(siren_stage(game.round, pellets.eaten_fraction())))
+    };
+
+    let returning_eyes: Vec<&UnitPosition> = query_ghosts
+        .iter()
+        .filter(|(ghost, _)| ghost.is_returning_to_house())
+        .map(|(_, pos)| pos)
+        .collect();
+    let eyes_pos = (!returning_eyes.is_empty()).then(|| UnitPosition {
+        x: returning_eyes.iter().map(|pos| pos.x).sum::<i32>() / returning_eyes.len() as i32,
+        y: returning_eyes.iter().map(|pos| pos.y).sum::<i32>() / returning_eyes.len() as i32,
+    });
+
+    // Start the loop of the current track
+    if let Some(track) = track {
+        if !ambient.loops.iter().any(|ambient_loop| ambient_loop.track == track) {
+            let settings = PlaybackSettings::LOOP.with_volume(0.);
+            let sink = if let (AmbientTrack::Eyes, Some(pos)) = (track, eyes_pos) {
+                sounds.play_at_with_settings(SoundChannel::Siren, track.sound(), &pos, settings)
+            } else {
+                sounds.play_with_settings(SoundChannel::Siren, track.sound(), settings)
+            };
+            ambient.loops.push(AmbientLoop { track, sink, volume: 0. });
+        }
+    }
+
+    // Follow the eaten ghosts with the eyes loop
+    if let Some(pos) = eyes_pos {
+        for ambient_loop in ambient.loops.iter().filter(|ambient_loop| ambient_loop.track == AmbientTrack::Eyes) {
+            sounds.set_position(&ambient_loop.sink, &pos);
+        }
+    }
+
+    // Crossfade between the current and the previous tracks
+    let step = time.delta_seconds() / AMBIENT_CROSSFADE_DURATION;
+    for ambient_loop in ambient.loops.iter_mut() {
+        ambient_loop.volume = if Some(ambient_loop.track) == track {
+            (ambient_loop.volume + step).min(1.)
+        } else {
+            (ambient_loop.volume - step).max(0.)
+        };
+        sounds.set_volume(SoundChannel::Siren, &ambient_loop.sink, ambient_loop.volume);
+    }
+
+    // Stop the tracks which are faded out
+    ambient.loops.retain(|ambient_loop| {
+        let is_faded_out = ambient_loop.volume <= 0. && Some(ambient_loop.track) != track;
+        if is_faded_out {
+            sounds.stop_sink(SoundChannel::Siren, &ambient_loop.sink);
+        }
+        !is_faded_out
+    });
+}
+
+// The siren rises with the fraction of the dots eaten in the round, later
+// rounds start on a higher stage
+fn siren_stage(round: u32, eaten_fraction: f32) -> u32 {
+    let offset = round.saturating_sub(1).min(AMBIENT_SIREN_ROUND_OFFSET_MAX);
+    let rising_stages = AMBIENT_SIREN_STAGES - offset;
+    let stage = 1 + offset + (eaten_fraction * rising_stages as f32) as u32;
+    stage.min(AMBIENT_SIREN_STAGES)
+}
+
+pub fn apply_sound_volumes(
+    settings: Res<Settings>,
+    sounds: Sounds,
+) {
+    if settings.is_changed() {
+        sounds.apply_volumes();
+    }
+}
+
+// Cut the ambient sound immediately, e.g. when pacman dies or the game is paused
+pub fn stop_ambient_sound(
+    mut ambient: ResMut<AmbientSound>,
+    mut sounds: Sounds,
+) {
+    ambient.loops.clear();
+    sounds.stop(SoundChannel::Siren);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn siren_rises_with_the_dots_eaten_in_every_round() {
+        for round in [1, 2, 3, 4, 10] {
+            let first = siren_stage(round, 0.);
+            let last = siren_stage(round, 1.);
+            assert!(first < last, "round {}", round);
+            assert_eq!(last, AMBIENT_SIREN_STAGES);
+        }
+        assert_eq!(siren_stage(1, 0.), 1);
+        assert_eq!(siren_stage(1, 0.5), 3);
+        assert_eq!(siren_stage(10, 0.), AMBIENT_SIREN_ROUND_OFFSET_MAX + 1);
+        assert_eq!(siren_stage(10, 0.6), AMBIENT_SIREN_STAGES);
     }
 }
@@ -4,13 +4,22 @@
 
 use bevy::prelude::*;
 
+use crate::events::*;
 use crate::game::*;
 use crate::maze::*;
-use crate::sound::*;
+
+// The states are timed by the game clock, so the waits scale with the
+// game speed like the gameplay. Only the ready wait is timed by the real
+// time, the start jingle during it plays at the normal speed.
+const DURATION_READY: f32 = 5.;
 
 #[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash, States)]
 pub enum GameState {
+    // The assets are loaded before the game starts
     #[default]
+    Loading,
+    // Some assets could not be loaded, the game can not be started
+    LoadingFailed,
     Start,
     Ready,
     Running,
@@ -21,20 +30,13 @@ pub enum GameState {
     GameOver,
 }
 
-pub fn on_enter_state_start(
-    asset_server: Res<AssetServer>,
-    audio: Res<Audio>,
-) {
-    audio.play(asset_server.load("sounds/start.ogg"));
-}
-
 pub fn switch_state_to_new_round(
     mut game: ResMut<Game>,
     mut next_state: ResMut<NextState<GameState>>,
-    time: Res<Time>,
+    clock: Res<GameClock>,
 ) {
-    if time.elapsed_seconds() - game.elapsed_time_state > 0.05 {
-        game.elapsed_time_state = time.elapsed_seconds();
+    if clock.elapsed_seconds() - game.elapsed_time_state > 0.05 {
+        game.elapsed_time_state = clock.elapsed_seconds();
         next_state.set(GameState::NewRound);
     }
 }
@@ -42,10 +44,10 @@ pub fn switch_state_to_new_round(
 pub fn switch_state_to_ready(
     mut game: ResMut<Game>,
     mut next_state: ResMut<NextState<GameState>>,
-    time: Res<Time>,
+    clock: Res<GameClock>,
 ) {
-    if time.elapsed_seconds() - game.elapsed_time_state > 0.05 {
-        game.elapsed_time_state = time.elapsed_seconds();
+    if clock.elapsed_seconds() - game.elapsed_time_state > 0.05 {
+        game.elapsed_time_state = clock.elapsed_seconds();
         next_state.set(GameState::Ready);
     }
 }
@@ -53,10 +55,14 @@ pub fn switch_state_to_ready(
 pub fn switch_state_to_running(
     mut game: ResMut<Game>,
     mut next_state: ResMut<NextState<GameState>>,
+    clock: Res<GameClock>,
     time: Res<Time>,
+    mut elapsed_ready: Local<f32>,
 ) {
-    if time.elapsed_seconds() - game.elapsed_time_state > SOUND_DURATION_START {
-        game.elapsed_time_state = time.elapsed_seconds();
+    *elapsed_ready += time.delta_seconds();
+    if *elapsed_ready > DURATION_READY {
+        *elapsed_ready = 0.;
+        game.elapsed_time_state = clock.elapsed_seconds();
         next_state.set(GameState::Running);
     }
 }
@@ -64,10 +70,10 @@ pub fn switch_state_to_running(
 pub fn switch_state_to_respawn_or_game_over(
     mut game: ResMut<Game>,
     mut next_game_state: ResMut<NextState<GameState>>,
-    time: Res<Time>,
+    clock: Res<GameClock>,
 ) {
     game.lifes -= 1;
-    game.elapsed_time_state = time.elapsed_seconds();
+    game.elapsed_time_state = clock.elapsed_seconds();
     if game.lifes < 1 {
         next_game_state.set(GameState::GameOver);
     } else {
@@ -78,13 +84,14 @@ pub fn switch_state_to_respawn_or_game_over(
 pub fn switch_state_to_round_won(
     mut game: ResMut<Game>,
     mut next_game_state: ResMut<NextState<GameState>>,
-    query_dot: Query<&Dot>,
-    query_energizer: Query<&Energizer>,
-    time: Res<Time>,
+    mut round_won_events: EventWriter<RoundWon>,
+    pellets: Res<Pellets>,
+    clock: Res<GameClock>,
 ) {
-    if query_dot.is_empty() && query_energizer.is_empty() {
-        game.elapsed_time_state = time.elapsed_seconds();
+    if pellets.is_empty() {
+        game.elapsed_time_state = clock.elapsed_seconds();
         next_game_state.set(GameState::RoundWon);
+        round_won_events.send(RoundWon { round: game.round });
         game.round += 1;
     }
 }
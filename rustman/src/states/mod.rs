@@ -4,6 +4,8 @@
 
 use bevy::prelude::*;
 
+use crate::game::*;
+
 mod game_state;
 mod pacman_state;
 
@@ -19,10 +21,15 @@ impl Plugin for StatesPlugin {
             .add_state::<GameState>()
             .add_state::<PacmanState>()
 
+            // Simulation Ticks, state changes are applied right away so no
+            // further tick of the same frame runs in the old state
+            .add_systems((
+                apply_state_transition::<GameState>,
+                apply_state_transition::<PacmanState>,
+            ).chain().after(SimulationSet::Contacts).in_schedule(CoreSchedule::FixedUpdate))
+
             // Start State
             .add_systems((
-                on_enter_state_start
-                    .in_schedule(OnEnter(GameState::Start)),
                 switch_state_to_new_round
                     .in_set(OnUpdate(GameState::Start)),
             ))
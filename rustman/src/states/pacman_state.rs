@@ -4,11 +4,10 @@
 
 use bevy::prelude::*;
 
+use crate::game::*;
 use crate::ghosts::*;
 use crate::pacman::*;
 
-const DURATION_ENERGIZED: f32 = 8.;
-
 #[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash, States)]
 pub enum PacmanState {
     #[default]
@@ -19,13 +18,14 @@ pub enum PacmanState {
 }
 
 pub fn switch_pacman_state_to_energized(
-    time: Res<Time>,
+    game: Res<Game>,
+    clock: Res<GameClock>,
     query_pacman: Query<&Pacman>,
     mut next_pacman_state: ResMut<NextState<PacmanState>>,
 ) {
     if let Some(pacman) = query_pacman.iter().next() {
-        let elapsed_since_energized = time.elapsed_seconds() - pacman.start_time_energized;
-        if elapsed_since_energized > DURATION_ENERGIZED {
+        let elapsed_since_energized = clock.elapsed_seconds() - pacman.start_time_energized;
+        if elapsed_since_energized > game.round_spec().frightened_duration {
             next_pacman_state.set(PacmanState::Normal);
         }
     }
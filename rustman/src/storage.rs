@@ -0,0 +1,72 @@
+//
+// Daniel Bauer (bauerda@pm.me)
+//
+
+use bevy::prelude::*;
+use serde::{de::DeserializeOwned, Serialize};
+
+// Values are stored as RON, in the config directory of the user on native
+// platforms and in the local storage of the browser on the web
+
+pub fn load<T: DeserializeOwned>(key: &str) -> Option<T> {
+    let content = read(key)?;
+    match ron::from_str(&content) {
+        Ok(value) => Some(value),
+        Err(err) => {
+            warn!("Could not parse stored {}: {}", key, err);
+            None
+        }
+    }
+}
+
+pub fn save<T: Serialize>(key: &str, value: &T) {
+    match ron::ser::to_string_pretty(value, ron::ser::PrettyConfig::default()) {
+        Ok(content) => write(key, &content),
+        Err(err) => warn!("Could not serialize {}: {}", key, err),
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn storage_path(key: &str) -> std::path::PathBuf {
+    let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+        .map(std::path::PathBuf::from)
+        .or_else(|| std::env::var_os("APPDATA").map(std::path::PathBuf::from))
+        .or_else(|| std::env::var_os("HOME").map(|home| std::path::Path::new(&home).join(".config")))
+        .unwrap_or_default();
+    config_dir.join("rustman").join(format!("{}.ron", key))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn read(key: &str) -> Option<String> {
+    std::fs::read_to_string(storage_path(key)).ok()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn write(key: &str, content: &str) {
+    let path = storage_path(key);
+    if let Some(dir) = path.parent() {
+        let _ = std::fs::create_dir_all(dir);
+    }
+    if let Err(err) = std::fs::write(&path, content) {
+        warn!("Could not write {}: {}", path.display(), err);
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn local_storage() -> Option<web_sys::Storage> {
+    web_sys::window()?.local_storage().ok()?
+}
+
+#[cfg(target_arch = "wasm32")]
+fn read(key: &str) -> Option<String> {
+    local_storage()?.get_item(&format!("rustman.{}", key)).ok()?
+}
+
+#[cfg(target_arch = "wasm32")]
+fn write(key: &str, content: &str) {
+    if let Some(storage) = local_storage() {
+        if storage.set_item(&format!("rustman.{}", key), content).is_err() {
+            warn!("Could not write {} to the local storage", key);
+        }
+    }
+}
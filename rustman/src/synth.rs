@@ -0,0 +1,181 @@
+//
+// Daniel Bauer (bauerda@pm.me)
+//
+
+use std::f32::consts::TAU;
+use std::sync::Arc;
+use std::time::Duration;
+
+use bevy::asset::{AssetLoader, LoadContext, LoadedAsset};
+use bevy::audio::{Decodable, Source};
+use bevy::reflect::TypeUuid;
+use bevy::utils::{BoxedFuture, HashMap};
+use serde::Deserialize;
+
+const SYNTH_SAMPLE_RATE: u32 = 22050;
+
+// A sound synthesized from its parameters, the samples are rendered once
+// when the sound pack is loaded
+#[derive(TypeUuid)]
+#[uuid = "c4d2a3f0-5b7e-4e51-9a0c-3f8e6d1b2a47"]
+pub struct SynthSound {
+    samples: Arc<[f32]>,
+}
+
+#[derive(Clone, Copy, Deserialize)]
+pub enum Waveform {
+    Square,
+    Triangle,
+    Sawtooth,
+    Sine,
+    Noise,
+}
+
+// The parameters of a sound, which consists of segments played one after
+// another, each sweeping the frequency and the volume from start to end
+#[derive(Deserialize)]
+pub struct SynthParams {
+    pub waveform: Waveform,
+    #[serde(default = "default_volume")]
+    pub volume: f32,
+    pub segments: Vec<SynthSegment>,
+}
+
+#[derive(Deserialize)]
+pub struct SynthSegment {
+    pub duration: f32,
+    pub frequency: (f32, f32),
+    #[serde(default = "default_volume_sweep")]
+    pub volume: (f32, f32),
+    // Rate and depth of a frequency modulation in Hz
+    #[serde(default)]
+    pub vibrato: (f32, f32),
+}
+
+fn default_volume() -> f32 {
+    1.
+}
+
+fn default_volume_sweep() -> (f32, f32) {
+    (1., 1.)
+}
+
+impl SynthParams {
+    pub fn render(&self) -> SynthSound {
+        let mut samples = Vec::new();
+        let mut phase = 0.;
+        let mut noise = NoiseGenerator::default();
+        for segment in self.segments.iter() {
+            let length = (segment.duration * SYNTH_SAMPLE_RATE as f32) as usize;
+            for i in 0..length {
+                let t = i as f32 / length as f32;
+                let time = i as f32 / SYNTH_SAMPLE_RATE as f32;
+                let vibrato = segment.vibrato.1 * (TAU * segment.vibrato.0 * time).sin();
+                let frequency = lerp(segment.frequency, t) + vibrato;
+                phase = (phase + frequency / SYNTH_SAMPLE_RATE as f32).fract();
+
+                let value = match self.waveform {
+                    Waveform::Square => if phase < 0.5 { 1. } else { -1. },
+                    Waveform::Triangle => 1. - 4. * (phase - 0.5).abs(),
+                    Waveform::Sawtooth => 2. * phase - 1.,
+                    Waveform::Sine => (TAU * phase).sin(),
+                    Waveform::Noise => noise.next(),
+                };
+                samples.push(value * lerp(segment.volume, t) * self.volume);
+            }
+        }
+        SynthSound { samples: samples.into() }
+    }
+}
+
+fn lerp((start, end): (f32, f32), t: f32) -> f32 {
+    start + (end - start) * t
+}
+
+// Deterministic white noise, so a sound pack always sounds the same
+struct NoiseGenerator(u32);
+
+impl Default for NoiseGenerator {
+    fn default() -> Self {
+        Self(0x1234_5678)
+    }
+}
+
+impl NoiseGenerator {
+    fn next(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0 as f32 / u32::MAX as f32 * 2. - 1.
+    }
+}
+
+impl Decodable for SynthSound {
+    type DecoderItem = f32;
+    type Decoder = SynthDecoder;
+
+    fn decoder(&self) -> Self::Decoder {
+        SynthDecoder {
+            samples: self.samples.clone(),
+            index: 0,
+        }
+    }
+}
+
+pub struct SynthDecoder {
+    samples: Arc<[f32]>,
+    index: usize,
+}
+
+impl Iterator for SynthDecoder {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let sample = self.samples.get(self.index).copied();
+        self.index += 1;
+        sample
+    }
+}
+
+impl Source for SynthDecoder {
+    fn current_frame_len(&self) -> Option<usize> {
+        Some(self.samples.len().saturating_sub(self.index))
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        SYNTH_SAMPLE_RATE
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        Some(Duration::from_secs_f32(self.samples.len() as f32 / SYNTH_SAMPLE_RATE as f32))
+    }
+}
+
+// Loads a sound pack, a RON map from sound names to their parameters.
+// Each sound is a labeled asset, e.g. "sounds/arcade.synth.ron#eat".
+#[derive(Default)]
+pub struct SynthPackLoader;
+
+impl AssetLoader for SynthPackLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let pack: HashMap<String, SynthParams> = ron::de::from_bytes(bytes)?;
+            for (name, params) in pack.iter() {
+                load_context.set_labeled_asset(name, LoadedAsset::new(params.render()));
+            }
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["synth.ron"]
+    }
+}
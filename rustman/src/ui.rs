@@ -4,188 +4,320 @@
 
 use bevy::prelude::*;
 
-use super::states::*;
 use super::game::*;
+use super::packs::*;
+use super::settings::*;
+use super::scaling::*;
+use super::states::*;
 
 pub const UI_HEIGHT: u32 = 100;
 
+const HUD_FONT: &str = "font.hud";
+const HUD_FONT_SIZE: f32 = 16.;
+const HUD_FONT_SIZE_LARGE: f32 = 21.;
+const HUD_ICON_SIZE: f32 = 22.;
+const HUD_PADDING: f32 = 25.;
+const HUD_BLINK_DURATION: f32 = 0.25;
+const HUD_FRUIT_ROUNDS: u32 = 7;
+
+// The layout of the HUD, a bar above and below the maze with a left,
+// a center and a right slot, the elements are placed in the slots in
+// the order of this list
+const HUD_LAYOUT: [(HudSlot, HudElement); 9] = [
+    (HudSlot::TopLeft, HudElement::OneUp),
+    (HudSlot::TopLeft, HudElement::Score),
+    (HudSlot::TopCenter, HudElement::HighScoreLabel),
+    (HudSlot::TopCenter, HudElement::HighScore),
+    (HudSlot::TopRight, HudElement::Status),
+    (HudSlot::BottomLeft, HudElement::Lifes),
+    (HudSlot::BottomCenter, HudElement::Round),
+    (HudSlot::BottomCenter, HudElement::Speed),
+    (HudSlot::BottomRight, HudElement::Fruits),
+];
+
 pub struct UiPlugin;
 
 impl Plugin for UiPlugin {
     fn build(&self, app: &mut App) {
         app
-            .add_startup_system(setup_ui)
+            .add_system(setup_ui.in_schedule(OnEnter(GameState::Start)))
             .add_systems((
-                ui_update_status,
-                ui_update_round_text,
-                ui_update_points_text,
-                ui_update_life_text,
+                ui_update_layout,
+                ui_update_texts,
+                ui_update_icons,
             ))
         ;
     }
 }
 
-#[derive(Component)]
-pub struct StatusText;
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum HudSlot {
+    TopLeft,
+    TopCenter,
+    TopRight,
+    BottomLeft,
+    BottomCenter,
+    BottomRight,
+}
 
-#[derive(Component)]
-pub struct PointsText;
+const HUD_SLOTS: [HudSlot; 6] = [
+    HudSlot::TopLeft,
+    HudSlot::TopCenter,
+    HudSlot::TopRight,
+    HudSlot::BottomLeft,
+    HudSlot::BottomCenter,
+    HudSlot::BottomRight,
+];
+
+impl HudSlot {
+    fn is_top(self) -> bool {
+        matches!(self, HudSlot::TopLeft | HudSlot::TopCenter | HudSlot::TopRight)
+    }
+
+    fn align_items(self) -> AlignItems {
+        match self {
+            HudSlot::TopLeft | HudSlot::BottomLeft => AlignItems::FlexStart,
+            HudSlot::TopCenter | HudSlot::BottomCenter => AlignItems::Center,
+            HudSlot::TopRight | HudSlot::BottomRight => AlignItems::FlexEnd,
+        }
+    }
+}
+
+#[derive(Component, Clone, Copy, PartialEq, Eq)]
+pub enum HudElement {
+    OneUp,
+    Score,
+    HighScoreLabel,
+    HighScore,
+    Status,
+    Lifes,
+    Round,
+    Speed,
+    Fruits,
+}
+
+impl HudElement {
+    fn is_icon_row(self) -> bool {
+        matches!(self, HudElement::Lifes | HudElement::Fruits)
+    }
+
+    fn color(self) -> Color {
+        match self {
+            HudElement::Status => Color::YELLOW,
+            HudElement::Round | HudElement::Speed => Color::GRAY,
+            _ => Color::WHITE,
+        }
+    }
+
+    fn text(self, game: &Game, high_score: &HighScore, state: GameState) -> String {
+        match self {
+            HudElement::OneUp => "1UP".to_string(),
+            HudElement::Score => format!("{:>2}", game.points),
+            HudElement::HighScoreLabel => "HIGH SCORE".to_string(),
+            HudElement::HighScore => {
+                // The speed is only shown for high scores not made at the normal speed
+                let (points, speed_percent) = if game.points > high_score.points {
+                    (game.points, game.speed_percent)
+                } else {
+                    (high_score.points, high_score.speed_percent)
+                };
+                if speed_percent == 100 {
+                    format!("{:>2}", points)
+                } else {
+                    format!("{:>2} ({}%)", points, speed_percent)
+                }
+            }
+            HudElement::Status => match state {
+                GameState::Ready => "READY!",
+                GameState::Paused => "PAUSED!",
+                GameState::GameOver => "GAME OVER!",
+                _ => "",
+            }.to_string(),
+            HudElement::Round => format!("ROUND {}", game.round),
+            HudElement::Speed => format!("SPEED {}%", game.speed_percent),
+            HudElement::Lifes | HudElement::Fruits => String::new(),
+        }
+    }
+}
 
 #[derive(Component)]
-pub struct RoundText;
+struct HudRoot;
 
 #[derive(Component)]
-pub struct LifesText;
-
-fn setup_ui(mut commands: Commands, asset_server: Res<AssetServer>) {
-    // Status Text
-    commands.spawn((
-        TextBundle::from_sections([
-            TextSection::from_style(TextStyle {
-                font: asset_server.load("fonts/FiraSans-Bold.ttf"),
-                font_size: 30.0,
-                color: Color::YELLOW,
-            }),
-        ])
-        .with_text_alignment(TextAlignment::Right)
-        .with_style(Style {
-            position_type: PositionType::Absolute,
-            position: UiRect {
-                top: Val::Px(20.0),
-                right: Val::Px(25.0),
-                ..default()
-            },
-            ..default()
-        }),
-        StatusText,
-    ));
-
-    // Round Text
-    commands.spawn((
-        TextBundle::from_sections([
-            TextSection::new(
-                "Round: ",
-                TextStyle {
-                    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
-                    font_size: 15.0,
-                    color: Color::GRAY,
+struct HudBar;
+
+fn setup_ui(mut commands: Commands, asset_server: Res<AssetServer>, pack_assets: Res<PackAssets>) {
+    let font = pack_assets.load(&asset_server, HUD_FONT);
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                    flex_direction: FlexDirection::Column,
+                    justify_content: JustifyContent::SpaceBetween,
+                    align_items: AlignItems::Center,
+                    ..default()
                 },
-            ),
-            TextSection::from_style(TextStyle {
-                font: asset_server.load("fonts/FiraMono-Medium.ttf"),
-                font_size: 15.0,
-                color: Color::GRAY,
-            }),
-        ])
-        .with_style(Style {
-            position_type: PositionType::Absolute,
-            position: UiRect {
-                top: Val::Px(20.0),
-                left: Val::Px(25.0),
                 ..default()
             },
-            ..default()
-        }),
-        RoundText,
-    ));
-
-    // Points Text
-    commands.spawn((
-        TextBundle::from_sections([
-            TextSection::new(
-                "Points: ",
-                TextStyle {
-                    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
-                    font_size: 15.0,
-                    color: Color::GRAY,
+            HudRoot,
+        ))
+        .with_children(|root| {
+            for is_top in [true, false] {
+                root
+                    .spawn((
+                        NodeBundle {
+                            style: Style {
+                                size: Size::new(Val::Percent(100.), Val::Px(UI_HEIGHT as f32 / 2.)),
+                                justify_content: JustifyContent::SpaceBetween,
+                                align_items: AlignItems::Center,
+                                padding: UiRect::horizontal(Val::Px(HUD_PADDING)),
+                                ..default()
+                            },
+                            ..default()
+                        },
+                        HudBar,
+                    ))
+                    .with_children(|bar| {
+                        for slot in HUD_SLOTS.into_iter().filter(|slot| slot.is_top() == is_top) {
+                            bar
+                                .spawn(NodeBundle {
+                                    style: Style {
+                                        flex_direction: FlexDirection::Column,
+                                        flex_basis: Val::Px(0.),
+                                        flex_grow: 1.,
+                                        align_items: slot.align_items(),
+                                        ..default()
+                                    },
+                                    ..default()
+                                })
+                                .with_children(|parent| {
+                                    for (_, element) in HUD_LAYOUT.iter().filter(|(s, _)| *s == slot) {
+                                        spawn_hud_element(parent, *element, &font);
+                                    }
+                                });
+                        }
+                    });
+            }
+        });
+}
+
+fn spawn_hud_element(parent: &mut ChildBuilder, element: HudElement, font: &Handle<Font>) {
+    if element.is_icon_row() {
+        parent.spawn((
+            NodeBundle {
+                style: Style {
+                    size: Size::height(Val::Px(HUD_ICON_SIZE)),
+                    gap: Size::width(Val::Px(4.)),
+                    ..default()
                 },
-            ),
-            TextSection::from_style(TextStyle {
-                font: asset_server.load("fonts/FiraMono-Medium.ttf"),
-                font_size: 15.0,
-                color: Color::GRAY,
-            }),
-        ])
-        .with_style(Style {
-            position_type: PositionType::Absolute,
-            position: UiRect {
-                top: Val::Px(40.0),
-                left: Val::Px(25.0),
                 ..default()
             },
-            ..default()
-        }),
-        PointsText,
-    ));
-
-    // Lifes Text
-    commands.spawn((
-        TextBundle::from_sections([
-            TextSection::new(
-                "Lifes: ",
+            element,
+        ));
+    } else {
+        parent.spawn((
+            TextBundle::from_section(
+                "",
                 TextStyle {
-                    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
-                    font_size: 15.0,
-                    color: Color::GRAY,
+                    font: font.clone(),
+                    font_size: HUD_FONT_SIZE,
+                    color: element.color(),
                 },
             ),
-            TextSection::from_style(TextStyle {
-                font: asset_server.load("fonts/FiraMono-Medium.ttf"),
-                font_size: 15.0,
-                color: Color::GRAY,
-            }),
-        ])
-        .with_style(Style {
-            position_type: PositionType::Absolute,
-            position: UiRect {
-                bottom: Val::Px(40.0),
-                left: Val::Px(25.0),
-                ..default()
-            },
-            ..default()
-        }),
-        LifesText,
-    ));
-}
-
-fn ui_update_status(
-    state: Res<State<GameState>>,
-    mut query: Query<&mut Text, With<StatusText>>,
-) {
-    if let Some(mut text) = query.iter_mut().next() {
-        let status = match state.0 {
-            GameState::Ready => "Ready!",
-            GameState::Paused => "Paused!",
-            GameState::GameOver => "Game Over!",
-            _ => "",
-        };
-        text.sections[0].value = status.to_string();
+            element,
+        ));
     }
 }
 
-fn ui_update_round_text(
-    game: Res<Game>,
-    mut query: Query<&mut Text, With<RoundText>>
+// Keep the bars as wide as the maze, which is narrower than wide windows,
+// and above the touch controls in portrait windows. The HUD is spawned
+// after loading, so it is also laid out when it was added.
+fn ui_update_layout(
+    scaling: Res<MazeScaling>,
+    query_window: Query<&Window>,
+    query_added: Query<(), Added<HudRoot>>,
+    mut query_root: Query<&mut Style, (With<HudRoot>, Without<HudBar>)>,
+    mut query_bars: Query<&mut Style, (With<HudBar>, Without<HudRoot>)>,
 ) {
-    if let Some(mut text) = query.iter_mut().next() {
-        text.sections[1].value = format!("{}", game.round);
+    if !scaling.is_changed() && query_added.is_empty() { return; }
+
+    let Ok(window) = query_window.get_single() else { return };
+    for mut style in query_bars.iter_mut() {
+        style.size.width = Val::Px(scaling.screen_width().min(window.width()));
+    }
+    for mut style in query_root.iter_mut() {
+        style.padding.bottom = Val::Px(scaling.controls_height());
     }
 }
 
-fn ui_update_points_text(
+fn ui_update_texts(
     game: Res<Game>,
-    mut query: Query<&mut Text, With<PointsText>>
+    high_score: Res<HighScore>,
+    settings: Res<Settings>,
+    state: Res<State<GameState>>,
+    mut query: Query<(&HudElement, &mut Text)>,
+    time: Res<Time>,
 ) {
-    if let Some(mut text) = query.iter_mut().next() {
-        text.sections[1].value = format!("{}", game.points);
+    for (element, mut text) in query.iter_mut() {
+        let value = element.text(&game, &high_score, state.0);
+        if text.sections[0].value != value {
+            text.sections[0].value = value;
+        }
+
+        let font_size = if settings.accessibility.is_large_text { HUD_FONT_SIZE_LARGE } else { HUD_FONT_SIZE };
+        if text.sections[0].style.font_size != font_size {
+            text.sections[0].style.font_size = font_size;
+        }
+
+        // The 1UP label blinks while the game is running
+        if *element == HudElement::OneUp {
+            let is_visible = state.0 != GameState::Running
+                || settings.accessibility.is_reduced_flash
+                || (time.elapsed_seconds() / (2. * HUD_BLINK_DURATION)).fract() < 0.5;
+            let color = if is_visible { element.color() } else { Color::NONE };
+            if text.sections[0].style.color != color {
+                text.sections[0].style.color = color;
+            }
+        }
     }
 }
 
-fn ui_update_life_text(
+// Show the spare lifes and the fruits of the recent rounds as icons
+fn ui_update_icons(
+    mut commands: Commands,
     game: Res<Game>,
-    mut query: Query<&mut Text, With<LifesText>>
+    asset_server: Res<AssetServer>,
+    pack_assets: Res<PackAssets>,
+    query: Query<(Entity, &HudElement)>,
+    mut shown: Local<Option<(u32, u32)>>,
 ) {
-    if let Some(mut text) = query.iter_mut().next() {
-        text.sections[1].value = format!("{}", game.lifes);
+    if query.is_empty() || *shown == Some((game.lifes, game.round)) { return; }
+    *shown = Some((game.lifes, game.round));
+
+    for (entity, element) in query.iter() {
+        let icons: Vec<&str> = match element {
+            HudElement::Lifes => vec!["icon.life"; game.lifes.saturating_sub(1) as usize],
+            HudElement::Fruits => (game.round.saturating_sub(HUD_FRUIT_ROUNDS - 1).max(1)..=game.round)
+                .map(|round| RoundSpec::get(round).fruit.get_icon_id())
+                .collect(),
+            _ => continue,
+        };
+
+        commands.entity(entity).despawn_descendants();
+        commands.entity(entity).with_children(|parent| {
+            for icon in icons {
+                parent.spawn(ImageBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(HUD_ICON_SIZE), Val::Px(HUD_ICON_SIZE)),
+                        ..default()
+                    },
+                    image: pack_assets.load(&asset_server, icon).into(),
+                    ..default()
+                });
+            }
+        });
     }
 }
@@ -6,6 +6,7 @@ use bevy::prelude::*;
 use rand::Rng;
 
 use super::collision::*;
+use super::maze::*;
 
 pub const UNIT_SIZE: u32 = 100;
 pub const UNIT_HITBOX_SIZE: u32 = 20;
@@ -22,7 +23,7 @@ impl UnitScale {
     }
 }
 
-#[derive(Component, Clone, Copy, PartialEq, Eq)]
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
 pub struct UnitPosition {
     pub x: i32,
     pub y: i32,
@@ -41,12 +42,139 @@ impl UnitPosition {
         teleport_tunnel(self);
     }
 
-    pub fn to_vec3(&self) -> Vec3 {
+    pub fn to_vec3(self) -> Vec3 {
         Vec3 { x: self.x as f32, y: self.y as f32, z: 0. }
     }
+
+    pub fn tile(&self) -> TilePosition {
+        TilePosition::from_unit(self)
+    }
+
+    pub fn is_at_tile_center(&self) -> bool {
+        *self == self.tile().to_unit()
+    }
 }
 
-#[derive(PartialEq, Copy, Clone)]
+// Position of a unit before it moved in the current simulation tick, the
+// unit is drawn between this and its current position
+#[derive(Component, Clone, Copy, PartialEq, Eq)]
+pub struct UnitLastPosition(pub UnitPosition);
+
+// Units move in whole pixels, the part of a pixel left over in a tick is
+// carried over to the next tick so the speed is kept exactly
+pub fn pixel_steps(speed: f32, delta: f32, remainder: &mut f32) -> u32 {
+    let distance = speed * delta + *remainder;
+    let steps = distance.floor();
+    *remainder = distance - steps;
+    steps as u32
+}
+
+// The positions a unit passed in the current simulation tick, starting at
+// its last position and one more for each pixel step. Contacts are checked
+// along them, so they follow the turns the unit took.
+#[derive(Component, Default)]
+pub struct UnitPath(Vec<UnitPosition>);
+
+impl UnitPath {
+    // Move the unit one pixel and add its new position to the path
+    pub fn step(&mut self, pos: &mut UnitPosition, direction: UnitDirection) {
+        pos.move_in_direction(direction);
+        self.0.push(*pos);
+    }
+
+    // A unit which has no path yet stays at its position
+    pub fn positions<'a>(&'a self, pos: &'a UnitPosition) -> &'a [UnitPosition] {
+        if self.0.is_empty() { std::slice::from_ref(pos) } else { &self.0 }
+    }
+
+    fn start(&mut self, pos: UnitPosition) {
+        self.0.clear();
+        self.0.push(pos);
+    }
+}
+
+pub fn store_unit_last_positions(
+    mut query_units: Query<(&UnitPosition, &mut UnitLastPosition, Option<&mut UnitPath>)>,
+) {
+    for (pos, mut last_pos, path) in query_units.iter_mut() {
+        last_pos.0 = *pos;
+        if let Some(mut path) = path {
+            path.start(*pos);
+        }
+    }
+}
+
+// Position of a tile in the maze grid, tiles outside of the grid are
+// valid as well, e.g. in the tunnel
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TilePosition {
+    pub column: i32,
+    pub row: i32,
+}
+
+impl TilePosition {
+    // The tile whose center is closest to the given position
+    pub fn from_unit(pos: &UnitPosition) -> Self {
+        Self {
+            column: ((pos.x as f32 - TILE_ORIGIN_X_Y) / TILE_SIZE).round() as i32,
+            row: ((pos.y as f32 - TILE_ORIGIN_X_Y) / TILE_SIZE).round() as i32,
+        }
+    }
+
+    // The position of the tile center, rounded to the pixel of the
+    // corridor line through it
+    pub fn to_unit(self) -> UnitPosition {
+        UnitPosition {
+            x: (TILE_ORIGIN_X_Y + self.column as f32 * TILE_SIZE).round() as i32,
+            y: (TILE_ORIGIN_X_Y + self.row as f32 * TILE_SIZE).round() as i32,
+        }
+    }
+
+    pub fn is_in_grid(&self) -> bool {
+        (0..TILE_COLUMNS).contains(&self.column) && (0..TILE_ROWS).contains(&self.row)
+    }
+
+    // A tile is walkable if a unit fits on its center
+    pub fn is_walkable(&self) -> bool {
+        unit_can_move(&self.to_unit())
+    }
+
+    pub fn ahead(self, direction: UnitDirection, tiles: i32) -> Self {
+        match direction {
+            UnitDirection::Left => Self { column: self.column - tiles, ..self },
+            UnitDirection::Right => Self { column: self.column + tiles, ..self },
+            UnitDirection::Up => Self { row: self.row + tiles, ..self },
+            UnitDirection::Down => Self { row: self.row - tiles, ..self },
+            UnitDirection::None => self,
+        }
+    }
+
+    pub fn neighbor(self, direction: UnitDirection) -> Self {
+        self.ahead(direction, 1)
+    }
+
+    pub fn walkable_neighbors(self) -> impl Iterator<Item = (UnitDirection, TilePosition)> {
+        UnitDirection::ALL
+            .into_iter()
+            .map(move |direction| (direction, self.neighbor(direction)))
+            .filter(|(_, tile)| tile.is_walkable())
+    }
+
+    // Intersections are walkable tiles where a unit can turn
+    pub fn is_intersection(self) -> bool {
+        let mut horizontal = false;
+        let mut vertical = false;
+        for (direction, _) in self.walkable_neighbors() {
+            match direction {
+                UnitDirection::Left | UnitDirection::Right => horizontal = true,
+                _ => vertical = true,
+            }
+        }
+        self.is_walkable() && horizontal && vertical
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
 pub enum UnitDirection {
     None,
     Left,
@@ -56,6 +184,13 @@ pub enum UnitDirection {
 }
 
 impl UnitDirection {
+    pub const ALL: [UnitDirection; 4] = [
+        UnitDirection::Up,
+        UnitDirection::Left,
+        UnitDirection::Down,
+        UnitDirection::Right,
+    ];
+
     pub fn opposite(self) -> Self {
         match self {
             Self::Left => Self::Right,
@@ -66,14 +201,44 @@ impl UnitDirection {
         }
     }
 
-    pub fn random() -> Self {
-        let variants = [
-            UnitDirection::Left,
-            UnitDirection::Up,
-            UnitDirection::Right,
-            UnitDirection::Down,
-        ];
-        let index = rand::thread_rng().gen_range(0..variants.len());
-        variants[index]
+    // The one of the values given for left, right, up and down, e.g. the
+    // sprite sequence facing the direction, no direction picks left
+    pub fn pick<T: Copy>(self, values: [T; 4]) -> T {
+        match self {
+            Self::Left | Self::None => values[0],
+            Self::Right => values[1],
+            Self::Up => values[2],
+            Self::Down => values[3],
+        }
+    }
+
+    pub fn random(rng: &mut impl Rng) -> Self {
+        let index = rng.gen_range(0..Self::ALL.len());
+        Self::ALL[index]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tile_centers_round_trip() {
+        for column in 0..TILE_COLUMNS {
+            for row in 0..TILE_ROWS {
+                let tile = TilePosition { column, row };
+                let pos = tile.to_unit();
+                assert_eq!(TilePosition::from_unit(&pos), tile);
+                assert!(pos.is_at_tile_center(), "tile {:?} at {},{}", tile, pos.x, pos.y);
+            }
+        }
+    }
+
+    #[test]
+    fn tile_centers_are_on_corridor_lines() {
+        // Every third tile is on a multiple of 200 pixels from the origin
+        assert_eq!(TilePosition { column: 51, row: 3 }.to_unit(), UnitPosition { x: 3550, y: 350 });
+        assert_eq!(TilePosition { column: 3, row: 9 }.to_unit(), UnitPosition { x: 350, y: 750 });
+        assert_eq!(TilePosition { column: 1, row: 1 }.to_unit(), UnitPosition { x: 217, y: 217 });
     }
 }